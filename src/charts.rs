@@ -0,0 +1,450 @@
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use plotters::{coord::Shift, prelude::*};
+use rusqlite::Connection;
+use std::path::Path;
+
+use crate::{
+    color_schemes,
+    config::Config,
+    stats::{self, StatsView},
+};
+
+// what a stats chart shows, the same on screen and when saved
+pub struct ChartSpec {
+    pub view: StatsView,
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+    // the project whose children the breakdown chart shows, top-level projects when None
+    pub breakdown_parent: Option<usize>,
+}
+
+pub type DrawResult<DB> = Result<(), DrawingAreaErrorKind<<DB as DrawingBackend>::ErrorType>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+fn rgb(color: &color_schemes::Color) -> RGBColor {
+    RGBColor(color.r, color.g, color.b)
+}
+
+pub fn draw<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> DrawResult<DB> {
+    let day_end_offset_hours = config.get_day_end_offset_hours();
+    match spec.view {
+        StatsView::Total => draw_total_chart(chart, conn, config, spec),
+        StatsView::ByProject => draw_project_chart(chart, conn, config, spec),
+        StatsView::Heatmap => draw_heatmap(chart, conn, config),
+        StatsView::HourOfDay => {
+            let hours = stats::hour_of_day(conn, day_end_offset_hours, spec.first, spec.last);
+            // label every third hour to leave some room
+            draw_distribution(chart, config, &hours, |hour| {
+                if hour % 3 == 0 {
+                    hour.to_string()
+                } else {
+                    String::new()
+                }
+            })
+        }
+        StatsView::Estimates => draw_estimates(chart, conn, config, spec),
+        StatsView::Weekday => {
+            let hours = stats::weekdays(conn, day_end_offset_hours, spec.first, spec.last);
+            draw_distribution(chart, config, &hours, |day| {
+                chrono::Weekday::try_from(day as u8)
+                    .map(|d| d.to_string())
+                    .unwrap_or_default()
+            })
+        }
+    }
+}
+
+// draws the chart on the whole area over the background of the color scheme
+pub fn render<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> DrawResult<DB> {
+    root.fill(&rgb(&config.get_color_scheme().bg_color))?;
+    draw(&mut ChartBuilder::on(root), conn, config, spec)?;
+    root.present()
+}
+
+pub fn save(
+    path: &Path,
+    format: ImageFormat,
+    size: (u32, u32),
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> Result<(), String> {
+    // the backends differ in their error types
+    match format {
+        ImageFormat::Png => render(
+            &BitMapBackend::new(path, size).into_drawing_area(),
+            conn,
+            config,
+            spec,
+        )
+        .map_err(|e| e.to_string()),
+        ImageFormat::Svg => render(
+            &SVGBackend::new(path, size).into_drawing_area(),
+            conn,
+            config,
+            spec,
+        )
+        .map_err(|e| e.to_string()),
+    }
+}
+
+fn draw_total_chart<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> DrawResult<DB> {
+    let day_end_offset_hours = config.get_day_end_offset_hours();
+    let (bucket, data) = stats::chart(conn, day_end_offset_hours, spec.first, spec.last);
+
+    let color = rgb(&config.get_color_scheme().sub_color);
+    let text_color = rgb(&config.get_color_scheme().text_color);
+
+    let style = ShapeStyle {
+        color: color.into(),
+        filled: true,
+        stroke_width: 2,
+    };
+
+    let x_min = data.first().unwrap().0;
+    // a single bucket still needs some width to draw
+    let x_max = data.last().unwrap().0.max(x_min.succ_opt().unwrap());
+
+    let y_max = data
+        .iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .unwrap()
+        .1
+        .max(1.0);
+
+    let mut chart = chart
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(30)
+        .build_cartesian_2d(x_min..x_max, 0.0_f32..y_max)?;
+
+    chart
+        .configure_mesh()
+        .label_style(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+        // take out the year display from the dates
+        .x_label_formatter(&|x| bucket.label(x))
+        .draw()?;
+
+    chart.draw_series(
+        AreaSeries::new(
+            data.iter().map(|x| *x), // The data iter
+            0.0,                     // Baseline
+            &color.mix(0.2),         // Make the series opac
+        )
+        .border_style(style), // Make a brighter border
+    )?;
+    Ok(())
+}
+
+// stacked bars, one color per project
+fn draw_project_chart<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> DrawResult<DB> {
+    let day_end_offset_hours = config.get_day_end_offset_hours();
+    let (bucket, days, series) = stats::by_project(
+        conn,
+        day_end_offset_hours,
+        spec.first,
+        spec.last,
+        spec.breakdown_parent,
+    );
+
+    let color_scheme = config.get_color_scheme();
+    let sub_color = rgb(&color_scheme.sub_color);
+    let text_color = rgb(&color_scheme.text_color);
+    let bg_color = rgb(&color_scheme.bg_color);
+
+    let y_max = (0..days.len())
+        .map(|i| series.iter().map(|s| s.hours[i]).sum::<f32>())
+        .fold(0.0, f32::max)
+        .max(1.0);
+
+    let mut chart = chart
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(30)
+        .build_cartesian_2d((0..days.len()).into_segmented(), 0.0_f32..y_max * 1.1)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .label_style(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => days.get(*i).map(|d| bucket.label(d)).unwrap_or_default(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    let mut bottoms = vec![0.0_f32; days.len()];
+    for s in &series {
+        // the same project keeps its color whatever else is shown
+        let color = match s.project_id {
+            Some(id) => Palette99::pick(id).to_rgba(),
+            None => sub_color.to_rgba(),
+        };
+        let bars = s
+            .hours
+            .iter()
+            .enumerate()
+            .map(|(i, hours)| {
+                let bottom = bottoms[i];
+                bottoms[i] += hours;
+                let mut bar = Rectangle::new(
+                    [
+                        (SegmentValue::Exact(i), bottom),
+                        (SegmentValue::Exact(i + 1), bottom + hours),
+                    ],
+                    color.filled(),
+                );
+                bar.set_margin(0, 0, 5, 5);
+                bar
+            })
+            .collect::<Vec<_>>();
+        chart
+            .draw_series(bars)?
+            .label(s.name.clone())
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+    }
+
+    if !series.is_empty() {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .label_font(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+            .background_style(bg_color.mix(0.8))
+            .border_style(text_color.mix(0.3))
+            .draw()?;
+    }
+    Ok(())
+}
+
+// a square per day of the last year, weeks as columns starting on Monday
+fn draw_heatmap<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    conn: &Connection,
+    config: &Config,
+) -> DrawResult<DB> {
+    let day_end_offset_hours = config.get_day_end_offset_hours();
+    let (first, hours) = stats::year_heatmap(conn, day_end_offset_hours);
+    let today = stats::today(day_end_offset_hours);
+    let weeks = ((today - first).num_days() / 7 + 1) as usize;
+
+    let color_scheme = config.get_color_scheme();
+    let text_color = rgb(&color_scheme.text_color);
+    let (low, high) = (&color_scheme.sub_alt_color, &color_scheme.main_color);
+    let blend = |t: f32| {
+        let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        RGBColor(
+            channel(low.r, high.r),
+            channel(low.g, high.g),
+            channel(low.b, high.b),
+        )
+    };
+
+    let max_hours = hours.values().copied().fold(0.0, f32::max);
+
+    let mut chart = chart
+        .margin(10)
+        .x_label_area_size(20)
+        .y_label_area_size(35)
+        .build_cartesian_2d((0..weeks).into_segmented(), (0..7_usize).into_segmented())?;
+
+    // rows are drawn bottom up, so Monday goes on the top row
+    let week_start = |week: usize| first + TimeDelta::weeks(week as i64);
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_labels(weeks)
+        .y_labels(7)
+        .label_style(TextStyle::from(("sans-serif", 12).into_font()).color(&text_color))
+        .x_label_formatter(&|x| match x {
+            // name the month in the column of its first Monday
+            SegmentValue::CenterOf(week) if week_start(*week).day() <= 7 => {
+                week_start(*week).format("%b").to_string()
+            }
+            _ => String::new(),
+        })
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(0) => "Sun".to_owned(),
+            SegmentValue::CenterOf(2) => "Fri".to_owned(),
+            SegmentValue::CenterOf(4) => "Wed".to_owned(),
+            SegmentValue::CenterOf(6) => "Mon".to_owned(),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    let days = first.iter_days().take_while(|day| *day <= today);
+    chart.draw_series(days.map(|day| {
+        let week = ((day - first).num_days() / 7) as usize;
+        let row = 6 - day.weekday().num_days_from_monday() as usize;
+        let day_hours = hours.get(&day).copied().unwrap_or_default();
+        let color = if max_hours > 0.0 {
+            blend(day_hours / max_hours)
+        } else {
+            blend(0.0)
+        };
+        let mut square = Rectangle::new(
+            [
+                (SegmentValue::Exact(week), SegmentValue::Exact(row)),
+                (SegmentValue::Exact(week + 1), SegmentValue::Exact(row + 1)),
+            ],
+            color.filled(),
+        );
+        square.set_margin(1, 1, 1, 1);
+        square
+    }))?;
+    Ok(())
+}
+
+// a bar per hour or weekday, label gives the name of the bar at an index
+fn draw_distribution<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    config: &Config,
+    hours: &[f32],
+    label: impl Fn(usize) -> String,
+) -> DrawResult<DB> {
+    let color = rgb(&config.get_color_scheme().sub_color);
+    let text_color = rgb(&config.get_color_scheme().text_color);
+
+    let y_max = hours.iter().copied().fold(0.0, f32::max).max(1.0);
+
+    let mut chart = chart
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(30)
+        .build_cartesian_2d((0..hours.len()).into_segmented(), 0.0_f32..y_max * 1.1)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(hours.len())
+        .label_style(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => label(*i),
+            _ => String::new(),
+        })
+        .draw()?;
+
+    chart.draw_series(hours.iter().enumerate().map(|(i, hours)| {
+        let mut bar = Rectangle::new(
+            [
+                (SegmentValue::Exact(i), 0.0),
+                (SegmentValue::Exact(i + 1), *hours),
+            ],
+            color.mix(0.6).filled(),
+        );
+        bar.set_margin(0, 0, 2, 2);
+        bar
+    }))?;
+    Ok(())
+}
+
+// actual pomodoros as a percentage of the estimate, a line per project. 100% is spot on
+fn draw_estimates<DB: DrawingBackend>(
+    chart: &mut ChartBuilder<DB>,
+    conn: &Connection,
+    config: &Config,
+    spec: &ChartSpec,
+) -> DrawResult<DB> {
+    let day_end_offset_hours = config.get_day_end_offset_hours();
+    let (bucket, days, series) =
+        stats::estimates(conn, day_end_offset_hours, spec.first, spec.last);
+
+    let color_scheme = config.get_color_scheme();
+    let sub_color = rgb(&color_scheme.sub_color);
+    let text_color = rgb(&color_scheme.text_color);
+    let bg_color = rgb(&color_scheme.bg_color);
+
+    let y_max = series
+        .iter()
+        .flat_map(|s| (0..days.len()).filter_map(|i| s.accuracy(i)))
+        .fold(100.0, f32::max);
+
+    let mut chart = chart
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d((0..days.len()).into_segmented(), 0.0_f32..y_max * 1.1)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .label_style(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(i) => days.get(*i).map(|d| bucket.label(d)).unwrap_or_default(),
+            _ => String::new(),
+        })
+        .y_label_formatter(&|y| format!("{y:.0}%"))
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        [
+            (SegmentValue::Exact(0), 100.0),
+            (SegmentValue::Exact(days.len()), 100.0),
+        ],
+        text_color.mix(0.4),
+    ))?;
+
+    for s in &series {
+        // the same colors as the project breakdown
+        let color = match s.project_id {
+            Some(id) => Palette99::pick(id).to_rgba(),
+            None => sub_color.to_rgba(),
+        };
+        let points: Vec<_> = (0..days.len())
+            .filter_map(|i| s.accuracy(i).map(|a| (SegmentValue::CenterOf(i), a)))
+            .collect();
+        chart.draw_series(LineSeries::new(points.clone(), color.stroke_width(2)))?;
+        chart
+            .draw_series(
+                points
+                    .into_iter()
+                    .map(|point| Circle::new(point, 4, color.filled())),
+            )?
+            .label(s.name.clone())
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled()));
+    }
+
+    if !series.is_empty() {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .label_font(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
+            .background_style(bg_color.mix(0.8))
+            .border_style(text_color.mix(0.3))
+            .draw()?;
+    }
+    Ok(())
+}
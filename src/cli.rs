@@ -0,0 +1,473 @@
+use chrono::{Datelike, Local, NaiveDate};
+use std::iter;
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::ipc;
+use crate::{
+    control::{self, Request},
+    export, import,
+    pomo::Pomo,
+    report, stats,
+};
+
+const USAGE: &str = "Usage: hello_work [COMMAND]
+
+Without a command the graphical interface is started.
+
+Commands:
+  start [--project NAME]         Start a work session
+  stop                           Stop the running session, saving it as partial if enabled
+  pause                          Pause or resume the running session
+  status [--json | --format TEMPLATE] [--follow]
+                                 Show the running session, --follow prints it every second.
+                                 TEMPLATE may use {label} {phase} {countdown} {remaining}
+                                 {progress} {project}, e.g. \"{countdown} {project}\"
+  switch NAME                    Switch the active project
+  projects list                  List projects with their total hours
+  tasks list [--project NAME]    List to-do tasks of the active or given project
+  tasks add NAME [--project NAME]
+                                 Add a to-do task to the active or given project
+  report [--week | --month | --from DATE --to DATE] [--format text|markdown|html]
+         [--output FILE]         Report hours worked in the last 7 or 30 days or between the
+                                 dates. Markdown and HTML reports add project totals and a
+                                 chart, a Markdown FILE gets its chart saved next to it as SVG
+  export [--format csv|json] [--from DATE] [--to DATE] [--output FILE]
+                                 Export work sessions, dates are YYYY-MM-DD and inclusive.
+                                 Writes to stdout without --output
+  import FILE [--yes]            Preview importing a Toggl or Clockify CSV export,
+                                 --yes records the sessions
+  help                           Show this message";
+
+// positional arguments and --flags of a command
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, Option<&'a str>)>,
+}
+
+impl<'a> Args<'a> {
+    // value_flags take the next argument as their value, switch_flags stand alone
+    fn parse(
+        args: &'a [String],
+        value_flags: &[&str],
+        switch_flags: &[&str],
+    ) -> Result<Self, String> {
+        let mut parsed = Args {
+            positional: vec![],
+            options: vec![],
+        };
+        let mut iter = args.iter().map(|a| a.as_str());
+        while let Some(arg) = iter.next() {
+            if value_flags.contains(&arg) {
+                let value = iter.next().ok_or(format!("{arg} needs a value"))?;
+                parsed.options.push((arg, Some(value)));
+            } else if switch_flags.contains(&arg) {
+                parsed.options.push((arg, None));
+            } else if arg.starts_with("--") {
+                return Err(format!("Unknown option {arg}"));
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+    fn value(&self, flag: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .find(|(f, _)| *f == flag)
+            .and_then(|(_, v)| *v)
+    }
+    fn has(&self, flag: &str) -> bool {
+        self.options.iter().any(|(f, _)| *f == flag)
+    }
+    fn no_positional(&self) -> Result<(), String> {
+        match self.positional.first() {
+            Some(arg) => Err(format!("Unexpected argument {arg}")),
+            None => Ok(()),
+        }
+    }
+}
+
+// Release builds on Windows have no console of their own, so the output would go nowhere.
+// Borrows the console of the terminal the command was typed in, if there is one
+#[cfg(windows)]
+pub fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // fails when there is no parent console or one is attached already, nothing to do then
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+// returns the process exit code
+pub fn run(args: &[String]) -> i32 {
+    match run_command(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("hello_work: {e}");
+            1
+        }
+    }
+}
+
+fn run_command(args: &[String]) -> Result<(), String> {
+    let (command, rest) = args.split_first().ok_or(USAGE)?;
+    match command.as_str() {
+        "start" => start(Args::parse(rest, &["--project"], &[])?),
+        "stop" => stop(Args::parse(rest, &[], &[])?),
+        "pause" => pause(Args::parse(rest, &[], &[])?),
+        "status" => status(Args::parse(rest, &["--format"], &["--json", "--follow"])?),
+        "switch" => switch(Args::parse(rest, &[], &[])?),
+        "projects" => projects(Args::parse(rest, &[], &[])?),
+        "tasks" => tasks(Args::parse(rest, &["--project"], &[])?),
+        "report" => report(Args::parse(
+            rest,
+            &["--from", "--to", "--format", "--output"],
+            &["--week", "--month"],
+        )?),
+        "import" => import(Args::parse(rest, &[], &["--yes"])?),
+        "export" => export(Args::parse(
+            rest,
+            &["--format", "--from", "--to", "--output"],
+            &[],
+        )?),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("Unknown command {command}\n\n{USAGE}")),
+    }
+}
+
+fn open_pomo() -> Result<Pomo, String> {
+    let mut pomo = Pomo::open().map_err(|e| e.to_string())?;
+    pomo.headless = true;
+    Ok(pomo)
+}
+
+// goes through the running app if there is one, so the two never fight over the session
+fn request(request: Request) -> Result<control::Status, String> {
+    #[cfg(unix)]
+    if let Some(response) = ipc::send(&request) {
+        let response = response?;
+        return match response.error {
+            Some(e) => Err(e),
+            None => response.status.ok_or("No status in response".to_owned()),
+        };
+    }
+
+    let mut pomo = open_pomo()?;
+    pomo.restore_recovered();
+    let result = control::execute(&mut pomo, request);
+    // nothing keeps time for a break without the app, so finishing work goes back to idle
+    if pomo.is_break() {
+        pomo.cancel_session();
+    }
+    result.map(|()| control::status(&pomo))
+}
+
+fn print_status(status: &control::Status) {
+    if status.running {
+        println!(
+            "{}  {}  {}",
+            status.label,
+            status.countdown,
+            status.project.as_deref().unwrap_or("No project")
+        );
+    } else {
+        println!("Idle");
+    }
+}
+
+fn start(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    let project = args.value("--project").map(|p| p.to_owned());
+    let status = request(Request::Start { project })?;
+    print_status(&status);
+    if let Some(warning) = &status.budget_warning {
+        eprintln!("{warning}");
+    }
+    Ok(())
+}
+
+fn stop(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    print_status(&request(Request::Stop)?);
+    Ok(())
+}
+
+fn pause(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    print_status(&request(Request::Pause)?);
+    Ok(())
+}
+
+// fills a template for status bars like waybar, polybar or i3blocks
+fn format_status(template: &str, status: &control::Status) -> String {
+    let phase = serde_json::to_value(status.phase).unwrap();
+    template
+        .replace("{label}", &status.label)
+        .replace("{phase}", phase.as_str().unwrap_or_default())
+        .replace("{countdown}", &status.countdown)
+        .replace("{remaining}", &status.remaining_secs.to_string())
+        .replace("{progress}", &format!("{:.0}", status.progress * 100.0))
+        .replace("{project}", status.project.as_deref().unwrap_or_default())
+}
+
+fn status(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    let print = |status: &control::Status| {
+        if args.has("--json") {
+            println!("{}", serde_json::to_string(status).unwrap());
+        } else if let Some(template) = args.value("--format") {
+            println!("{}", format_status(template, status));
+        } else {
+            print_status(status);
+        }
+    };
+
+    if !args.has("--follow") {
+        print(&request(Request::Status)?);
+        return Ok(());
+    }
+    loop {
+        // a bar keeps running the command, so errors are reported without quitting
+        match request(Request::Status) {
+            Ok(status) => print(&status),
+            Err(e) => eprintln!("hello_work: {e}"),
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn switch(args: Args) -> Result<(), String> {
+    if args.positional.is_empty() {
+        return Err(format!("Expected switch NAME\n\n{USAGE}"));
+    }
+    let project = args.positional.join(" ");
+    print_status(&request(Request::SwitchProject { project })?);
+    Ok(())
+}
+
+fn projects(args: Args) -> Result<(), String> {
+    let pomo = open_pomo()?;
+    match args.positional.as_slice() {
+        ["list"] => {
+            for (depth, p) in pomo.projects.get_all_tree_style() {
+                let name = (0..depth)
+                    .map(|_| "  ")
+                    .chain(iter::once("› "))
+                    .collect::<String>()
+                    + &p.name;
+                let hours = match p.target_hours {
+                    Some(target) => format!("{:>6.1} / {target}", p.total_hours),
+                    None => format!("{:>6.1}", p.total_hours),
+                };
+                match p.budget_string() {
+                    Some(budget) => println!("{name:<40} {hours:<14} {budget}"),
+                    None => println!("{name:<40} {hours}"),
+                }
+            }
+            Ok(())
+        }
+        _ => Err(format!("Expected projects list\n\n{USAGE}")),
+    }
+}
+
+fn tasks(args: Args) -> Result<(), String> {
+    match args.positional.as_slice() {
+        ["list"] => {
+            let mut pomo = open_pomo()?;
+            let project_id = match args.value("--project") {
+                Some(name) => Some(
+                    pomo.projects
+                        .find_by_name(name)
+                        .ok_or(format!("No project named {name}"))?
+                        .id,
+                ),
+                None => pomo.projects.get_active(),
+            };
+            pomo.tasks.switch_project(&pomo.db, project_id);
+            for (depth, task) in pomo.tasks.get_all_tree_style() {
+                let priority = task.priority.symbol();
+                let indent = "  ".repeat(depth);
+                let details: Vec<String> =
+                    [task.summary(), task.schedule().map(|s| format!("due {s}"))]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                if details.is_empty() {
+                    println!("{:>4} {priority} {indent}{}", task.id, task.name);
+                } else {
+                    println!(
+                        "{:>4} {priority} {indent}{}  ({})",
+                        task.id,
+                        task.name,
+                        details.join("; ")
+                    );
+                }
+            }
+            Ok(())
+        }
+        ["add", name @ ..] if !name.is_empty() => {
+            let name = name.join(" ");
+            let project = args.value("--project").map(|p| p.to_owned());
+            let status = request(Request::AddTask {
+                name: name.clone(),
+                project,
+            })?;
+            println!(
+                "Added {name} to {}",
+                args.value("--project")
+                    .or(status.project.as_deref())
+                    .unwrap_or("No project")
+            );
+            Ok(())
+        }
+        _ => Err(format!("Expected tasks list or tasks add NAME\n\n{USAGE}")),
+    }
+}
+
+fn report(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    let output = args.value("--output");
+    let format = match args
+        .value("--format")
+        .or(output.and_then(|o| o.rsplit_once('.').map(|(_, ext)| ext)))
+    {
+        Some("md" | "markdown") => Some(report::Format::Markdown),
+        Some("html") => Some(report::Format::Html),
+        Some("text" | "txt") | None => None,
+        Some(other) => return Err(format!("Unknown report format {other}")),
+    };
+
+    let pomo = open_pomo()?;
+    let offset = pomo.config.get_day_end_offset_hours();
+    let today = stats::today(offset);
+    let (from, to) = match (args.value("--from"), args.value("--to")) {
+        (Some(from), Some(to)) => (parse_date(from)?, parse_date(to)?),
+        (None, None) if args.has("--month") => (today - chrono::TimeDelta::days(29), today),
+        (None, None) if args.has("--week") => (today - chrono::TimeDelta::days(6), today),
+        _ => {
+            return Err(format!(
+                "Expected report --week, --month or --from DATE --to DATE\n\n{USAGE}"
+            ));
+        }
+    };
+    if from > to {
+        return Err("--from should not be after --to".to_owned());
+    }
+
+    let Some(format) = format else {
+        let (bucket, data) = stats::chart(&pomo.db, offset, from, to);
+        let text: String = data
+            .iter()
+            .map(|(day, hours)| match bucket {
+                stats::Bucket::Day => format!(
+                    "{} {:02}-{:02}  {hours:>5.1}\n",
+                    day.weekday(),
+                    day.month(),
+                    day.day()
+                ),
+                _ => format!("{:<9} {hours:>5.1}\n", bucket.label(day)),
+            })
+            .chain(iter::once(format!(
+                "Total     {:>5.1}\n",
+                data.iter().map(|x| x.1).sum::<f32>()
+            )))
+            .chain(iter::once(format!(
+                "Tasks     {:>5}\n",
+                stats::completed_tasks(&pomo.db, offset, from, to).len()
+            )))
+            .collect();
+        return match output {
+            Some(path) => std::fs::write(path, text).map_err(|e| format!("{path}: {e}")),
+            None => {
+                print!("{text}");
+                Ok(())
+            }
+        };
+    };
+
+    let mut out: Box<dyn std::io::Write> = match output {
+        Some(path) => Box::new(std::fs::File::create(path).map_err(|e| format!("{path}: {e}"))?),
+        None => Box::new(std::io::stdout()),
+    };
+    // a Markdown file links its chart, on stdout the chart goes inline
+    let chart_file = output
+        .filter(|_| format == report::Format::Markdown)
+        .map(|path| std::path::Path::new(path).with_extension("svg"));
+    report::write_report(
+        &pomo.db,
+        &pomo.config,
+        from,
+        to,
+        format,
+        chart_file.as_deref(),
+        &mut out,
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date {date}, expected YYYY-MM-DD"))
+}
+
+fn export(args: Args) -> Result<(), String> {
+    args.no_positional()?;
+    let output = args.value("--output");
+    // without --format the extension of the output file decides
+    let format = match args
+        .value("--format")
+        .or(output.and_then(|o| o.rsplit_once('.').map(|(_, ext)| ext)))
+    {
+        Some("json") => export::Format::Json,
+        Some("csv") | None => export::Format::Csv,
+        Some(other) => return Err(format!("Unknown export format {other}")),
+    };
+    let from = args
+        .value("--from")
+        .map_or(Ok(NaiveDate::default()), parse_date)?;
+    let to = args
+        .value("--to")
+        .map_or(Ok(Local::now().date_naive()), parse_date)?;
+
+    let pomo = open_pomo()?;
+    let offset = pomo.config.get_day_end_offset_hours();
+    let mut out: Box<dyn std::io::Write> = match output {
+        Some(path) => Box::new(std::fs::File::create(path).map_err(|e| format!("{path}: {e}"))?),
+        None => Box::new(std::io::stdout()),
+    };
+    let count = export::export_sessions(&pomo.db, from, to, offset, format, &mut out)
+        .map_err(|e| e.to_string())?;
+    if output.is_some() {
+        println!("Exported {count} sessions");
+    }
+    Ok(())
+}
+
+fn import(args: Args) -> Result<(), String> {
+    let [path] = args.positional.as_slice() else {
+        return Err(format!("Expected import FILE\n\n{USAGE}"));
+    };
+    let pomo = open_pomo()?;
+    let preview = import::preview(
+        &pomo.db,
+        std::path::Path::new(path),
+        &pomo.unrecorded_work(),
+    )?;
+    println!("{}", preview.summary());
+    if args.has("--yes") {
+        let count = import::apply(&pomo.db, &preview, &pomo.unrecorded_work())
+            .map_err(|e| e.to_string())?;
+        println!("Imported {count} sessions");
+    } else {
+        println!("Run again with --yes to import");
+    }
+    Ok(())
+}
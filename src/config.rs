@@ -1,10 +1,11 @@
+use chrono::NaiveDate;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 
-use crate::color_schemes;
+use crate::{color_schemes, stats::StatsRange};
 
 pub fn config_dir() -> PathBuf {
     let dir = ProjectDirs::from("moe", "msg", "Hello Work")
@@ -44,6 +45,32 @@ impl WindowGeometry {
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalUnit {
+    Hours,
+    Pomodoros,
+}
+
+impl GoalUnit {
+    pub const ALL: [GoalUnit; 2] = [GoalUnit::Hours, GoalUnit::Pomodoros];
+}
+
+impl std::fmt::Display for GoalUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GoalUnit::Hours => "hours",
+            GoalUnit::Pomodoros => "pomodoros",
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct DailyGoal {
+    pub amount: f32,
+    pub unit: GoalUnit,
+}
+
 // be careful when changing field names
 // we skip serializing on Option::None to avoid locking in default values
 #[derive(Deserialize, Serialize, Default)]
@@ -52,6 +79,13 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub session_length: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    short_break_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    long_break_length: Option<f64>,
+    // number of work sessions before a long break
+    #[serde(skip_serializing_if = "Option::is_none")]
+    long_break_interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub color_scheme_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub work_end_audio: Option<PathBuf>,
@@ -69,6 +103,15 @@ pub struct Config {
     mini_window_geometry: Option<WindowGeometry>,
     #[serde(skip_serializing_if = "Option::is_none")]
     last_active_project: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daily_goal: Option<DailyGoal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_range: Option<StatsRange>,
+    // the span picked for StatsRange::Custom, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_custom_from: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats_custom_to: Option<NaiveDate>,
 }
 
 impl Config {
@@ -116,6 +159,28 @@ impl Config {
             .map(|x| x.1)
             .unwrap_or(&color_schemes::CHAOS_THEORY)
     }
+    pub fn get_short_break_length(&self) -> f64 {
+        self.short_break_length.unwrap_or(5.0)
+    }
+    pub fn set_short_break_length(&mut self, short_break_length: f64, file_path: &PathBuf) {
+        self.short_break_length = Some(short_break_length);
+        self.write_config(file_path);
+    }
+    pub fn get_long_break_length(&self) -> f64 {
+        self.long_break_length.unwrap_or(15.0)
+    }
+    pub fn set_long_break_length(&mut self, long_break_length: f64, file_path: &PathBuf) {
+        self.long_break_length = Some(long_break_length);
+        self.write_config(file_path);
+    }
+    pub fn get_long_break_interval(&self) -> u32 {
+        // 0 would mean dividing by zero when picking the next break
+        self.long_break_interval.unwrap_or(4).max(1)
+    }
+    pub fn set_long_break_interval(&mut self, long_break_interval: u32, file_path: &PathBuf) {
+        self.long_break_interval = Some(long_break_interval);
+        self.write_config(file_path);
+    }
     pub fn get_todo_tasks_enabled(&self) -> bool {
         self.todo_tasks_enabled.unwrap_or(true)
     }
@@ -160,4 +225,26 @@ impl Config {
         self.last_active_project = Some(active);
         self.write_config(file_path);
     }
+    pub fn get_daily_goal(&self) -> Option<DailyGoal> {
+        self.daily_goal
+    }
+    pub fn set_daily_goal(&mut self, daily_goal: Option<DailyGoal>, file_path: &PathBuf) {
+        self.daily_goal = daily_goal;
+        self.write_config(file_path);
+    }
+    pub fn get_stats_range(&self) -> StatsRange {
+        self.stats_range.unwrap_or_default()
+    }
+    pub fn set_stats_range(&mut self, stats_range: StatsRange, file_path: &PathBuf) {
+        self.stats_range = Some(stats_range);
+        self.write_config(file_path);
+    }
+    pub fn get_stats_custom_span(&self) -> Option<(NaiveDate, NaiveDate)> {
+        self.stats_custom_from.zip(self.stats_custom_to)
+    }
+    pub fn set_stats_custom_span(&mut self, from: NaiveDate, to: NaiveDate, file_path: &PathBuf) {
+        self.stats_custom_from = Some(from);
+        self.stats_custom_to = Some(to);
+        self.write_config(file_path);
+    }
 }
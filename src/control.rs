@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pomo::{Phase, Pomo};
+
+// Commands understood by both the control socket and the command line.
+// On the wire these are JSON objects like {"cmd": "start", "project": "Thesis"}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Start {
+        project: Option<String>,
+    },
+    Stop,
+    Pause,
+    SwitchProject {
+        project: String,
+    },
+    AddTask {
+        name: String,
+        project: Option<String>,
+    },
+    Status,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Status {
+    pub running: bool,
+    pub paused: bool,
+    pub phase: Phase,
+    // human readable phase, e.g. "Work 2/4 (Paused)"
+    pub label: String,
+    pub countdown: String,
+    pub remaining_secs: u64,
+    // 0.0 to 1.0 through the current phase
+    pub progress: f32,
+    pub project: Option<String>,
+    // the active project is over its budget for the current period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_warning: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn new(result: Result<(), String>, status: Option<Status>) -> Self {
+        Response {
+            ok: result.is_ok(),
+            error: result.err(),
+            status,
+        }
+    }
+}
+
+pub fn status(pomo: &Pomo) -> Status {
+    let length = pomo.phase_length();
+    let elapsed = pomo
+        .session_elapsed()
+        .map_or(0, |e| e.as_secs())
+        .min(length);
+    Status {
+        running: pomo.is_running(),
+        paused: pomo.is_paused(),
+        phase: pomo.phase,
+        label: pomo.phase_string(),
+        countdown: pomo.countdown_string(),
+        remaining_secs: length - elapsed,
+        progress: if length > 0 {
+            elapsed as f32 / length as f32
+        } else {
+            0.0
+        },
+        project: pomo.projects.get_active_project().map(|p| p.name.clone()),
+        budget_warning: pomo.budget_warning(),
+    }
+}
+
+fn find_project(pomo: &Pomo, name: &str) -> Result<usize, String> {
+    pomo.projects
+        .find_by_name(name)
+        .map(|p| p.id)
+        .ok_or(format!("No project named {name}"))
+}
+
+pub fn execute(pomo: &mut Pomo, request: Request) -> Result<(), String> {
+    match request {
+        Request::Start { project } => {
+            if pomo.is_running() {
+                return Err("A session is already running".to_owned());
+            }
+            if let Some(project) = project {
+                let id = find_project(pomo, &project)?;
+                pomo.switch_project(id);
+            }
+            pomo.init_session();
+        }
+        Request::Stop => {
+            if !pomo.is_running() {
+                return Err("No session is running".to_owned());
+            }
+            let elapsed = pomo.session_elapsed().unwrap_or_default().as_secs();
+            if elapsed >= pomo.phase_length() {
+                pomo.check_finished();
+            } else {
+                pomo.cancel_session();
+            }
+        }
+        Request::Pause => {
+            if !pomo.is_running() {
+                return Err("No session is running".to_owned());
+            }
+            pomo.toggle_pause();
+        }
+        Request::SwitchProject { project } => {
+            let id = find_project(pomo, &project)?;
+            pomo.switch_project(id);
+        }
+        Request::AddTask { name, project } => {
+            let project_id = match project {
+                Some(project) => Some(find_project(pomo, &project)?),
+                None => pomo.projects.get_active(),
+            };
+            pomo.tasks.add(name, project_id, None, &pomo.db);
+        }
+        Request::Status => {
+            pomo.check_finished();
+        }
+    }
+    Ok(())
+}
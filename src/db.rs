@@ -1,35 +1,51 @@
 use std::path::Path;
 
-use chrono::{Local, NaiveDate, NaiveTime, TimeDelta};
-use rusqlite::{Connection, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeDelta};
+use rusqlite::{Connection, OptionalExtension, Result};
 
-use crate::{projects::Project, todo_tasks::TodoTask};
+use crate::{
+    migrations::{self, MigrationError},
+    projects::{BudgetPeriod, Project},
+    todo_tasks::{Priority, Recurrence, TodoTask},
+};
 
-pub fn init_db(path: &Path) -> Connection {
-    let conn = Connection::open(path).expect("Failed to open database");
-    conn.execute_batch(include_str!("schema.sql")).unwrap();
-    conn
+pub fn init_db(path: &Path) -> Result<Connection, MigrationError> {
+    let mut conn = Connection::open(path)?;
+    migrations::migrate(&mut conn, Some(path))?;
+    Ok(conn)
 }
 
-pub fn get_projects(db: &Connection) -> Result<Vec<Project>> {
-    let mut stmt = db.prepare(
-        "WITH RECURSIVE project_aggregates AS (
+// Prepends the project_aggregates table, one row per project and each of its ancestors with
+// the work recorded on the project, so summing per id gives the totals with sub-projects.
+// Only work starting from ?1 up to ?2 counts, a NULL bound leaves that side open
+fn with_project_aggregates(query: &str) -> String {
+    format!(
+        "WITH RECURSIVE range_totals AS (
+            SELECT
+                project_id,
+                SUM(duration) AS duration
+            FROM
+                work
+            WHERE
+                (?1 IS NULL OR time_start >= ?1) AND (?2 IS NULL OR time_start < ?2)
+            GROUP BY
+                project_id
+        ),
+        project_aggregates AS (
             SELECT
                 p.id,
                 p.parent,
-                p.id AS root_project_id,
-                COALESCE(wt.duration, 0) AS duration_contribution
+                COALESCE(rt.duration, 0) AS duration_contribution
             FROM
                 projects p
             LEFT JOIN
-                work_totals wt ON p.id = wt.project_id
-            
+                range_totals rt ON p.id = rt.project_id
+
             UNION ALL
-            
+
             SELECT
                 p.id,
                 p.parent,
-                h.root_project_id,
                 h.duration_contribution
             FROM
                 projects p
@@ -37,13 +53,21 @@ pub fn get_projects(db: &Connection) -> Result<Vec<Project>> {
                 project_aggregates h ON p.id = h.parent
         )
 
-        SELECT
+        {query}"
+    )
+}
+
+pub fn get_projects(db: &Connection) -> Result<Vec<Project>> {
+    let mut stmt = db.prepare(&with_project_aggregates(
+        "SELECT
             p.id,
             p.name,
             p.target_hours,
             p.parent,
             GROUP_CONCAT(c.id) as children,
-            total_duration
+            total_duration,
+            p.budget_hours,
+            p.budget_period
         FROM
             projects p
         LEFT JOIN
@@ -62,9 +86,9 @@ pub fn get_projects(db: &Connection) -> Result<Vec<Project>> {
             p.archived = 0
         GROUP BY
             p.id;",
-    )?;
+    ))?;
     let projects = stmt
-        .query_map([], |row| {
+        .query_map((None::<i64>, None::<i64>), |row| {
             Ok(Project {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -75,34 +99,119 @@ pub fn get_projects(db: &Connection) -> Result<Vec<Project>> {
                     .map(|s| s.split(",").map(|x| x.parse().unwrap()).collect())
                     .unwrap_or_default(),
                 total_hours: row.get::<_, f32>(5)? / (60.0 * 60.0),
+                budget_hours: row.get(6)?,
+                budget_period: row
+                    .get::<_, Option<String>>(7)?
+                    .and_then(|s| BudgetPeriod::parse(&s)),
+                budget_used_hours: 0.0,
             })
         })?
         .collect();
     projects
 }
 
-pub fn add_project(db: &Connection, parent: Option<usize>) -> Result<usize> {
+pub struct ProjectHours {
+    pub id: usize,
+    pub name: String,
+    pub parent: Option<usize>,
+    pub archived: bool,
+    // sub-projects included
+    pub hours: f32,
+}
+
+// like get_projects but only counting work between start and end, projects without any are
+// left out, archived ones are kept
+pub fn get_project_hours_between(
+    db: &Connection,
+    start: i64,
+    end: i64,
+) -> Result<Vec<ProjectHours>> {
+    let mut stmt = db.prepare(&with_project_aggregates(
+        "SELECT
+            p.id,
+            p.name,
+            p.parent,
+            p.archived,
+            SUM(a.duration_contribution) AS total_duration
+        FROM
+            projects p
+        INNER JOIN
+            project_aggregates a ON a.id = p.id
+        GROUP BY
+            p.id
+        HAVING
+            total_duration > 0",
+    ))?;
+    stmt.query_map((start, end), |row| {
+        Ok(ProjectHours {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            parent: row.get(2)?,
+            archived: row.get(3)?,
+            hours: row.get::<_, f32>(4)? / (60.0 * 60.0),
+        })
+    })?
+    .collect()
+}
+
+pub fn add_project(db: &Connection, name: &str, parent: Option<usize>) -> Result<usize> {
     db.query_row(
         "INSERT INTO projects (name, parent) VALUES (?1, ?2) RETURNING id",
-        ("", parent),
+        (name, parent),
         |row| row.get(0),
     )
 }
 
+pub fn find_project(db: &Connection, name: &str, parent: Option<usize>) -> Result<Option<usize>> {
+    db.query_row(
+        "SELECT id FROM projects WHERE name = ?1 AND parent IS ?2 AND archived = 0",
+        (name, parent),
+        |row| row.get(0),
+    )
+    .optional()
+}
+
 pub fn update_project(db: &Connection, project: &Project) -> Result<usize> {
     db.execute(
         "UPDATE projects
-        SET name = ?2, target_hours = ?3, parent = ?4
+        SET name = ?2, target_hours = ?3, parent = ?4, budget_hours = ?5, budget_period = ?6
         WHERE id = ?1",
         (
             project.id,
             &project.name,
             project.target_hours,
             project.parent,
+            project.budget_hours,
+            project.budget_period.map(|p| p.as_str()),
         ),
     )
 }
 
+// hours recorded on the project and its sub-projects since start
+pub fn get_project_hours_since(db: &Connection, id: usize, start: i64) -> Result<f32> {
+    db.query_row::<Option<f32>, _, _>(
+        "WITH RECURSIVE descendants(id) AS (
+            SELECT ?1
+
+            UNION ALL
+
+            SELECT
+                p.id
+            FROM
+                projects p
+            INNER JOIN
+                descendants d ON p.parent = d.id
+        )
+
+        SELECT SUM(duration)
+        FROM work
+        WHERE project_id IN descendants AND time_start >= ?2",
+        (id, start),
+        |row| row.get(0),
+    )
+    .map(|secs| secs.unwrap_or(0.0) / (60.0 * 60.0))
+}
+
 pub fn archive_project(db: &Connection, id: usize) -> Result<usize> {
     // permanently delete if the project has no desdendants or any recorded session
     let recorded_session_count: usize = db.query_row(
@@ -128,85 +237,453 @@ pub struct WorkSession {
     pub time_start: u64,
     pub duration: u64,
     pub project_id: Option<usize>,
+    pub task_id: Option<usize>,
+    // the pomodoro ran to the end, as opposed to partial, imported or manual sessions
+    pub completed: bool,
 }
 
 pub fn add_work_session(db: &Connection, work_session: &WorkSession) -> Result<usize> {
     db.execute(
-        "INSERT INTO work (time_start, duration, project_id) VALUES (?1, ?2, ?3)",
+        "INSERT INTO work (time_start, duration, project_id, task_id, completed)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
         (
             work_session.time_start,
             work_session.duration,
             work_session.project_id,
+            work_session.task_id,
+            work_session.completed,
         ),
     )
 }
 
-pub fn get_work_hours_for_day(
+pub fn get_work_sessions(db: &Connection, start: i64, end: i64) -> Result<Vec<WorkSession>> {
+    let mut stmt = db.prepare(
+        "SELECT
+            time_start,
+            duration,
+            project_id,
+            task_id,
+            completed
+        FROM
+            work
+        WHERE
+            time_start >= ?1 AND time_start < ?2
+        ORDER BY
+            time_start",
+    )?;
+    stmt.query_map((start, end), |row| {
+        Ok(WorkSession {
+            time_start: row.get(0)?,
+            duration: row.get(1)?,
+            project_id: row.get(2)?,
+            task_id: row.get(3)?,
+            completed: row.get(4)?,
+        })
+    })?
+    .collect()
+}
+
+// time_start is the key, only the duration and project can change.
+// Moving a session to another project unlinks it from its task
+pub fn update_work_session(
     db: &Connection,
-    day: &NaiveDate,
-    config_offset_hours: u32,
-) -> Result<f32> {
+    time_start: u64,
+    duration: u64,
+    project_id: Option<usize>,
+) -> Result<usize> {
+    db.execute(
+        "UPDATE work
+        SET
+            duration = ?2,
+            project_id = ?3,
+            task_id = CASE WHEN project_id IS ?3 THEN task_id ELSE NULL END
+        WHERE time_start = ?1",
+        (time_start, duration, project_id),
+    )
+}
+
+pub fn delete_work_session(db: &Connection, time_start: u64) -> Result<usize> {
+    db.execute("DELETE FROM work WHERE time_start = ?1", (time_start,))
+}
+
+// any recorded session sharing time with start..end, other than the one starting at ignored
+pub fn work_overlaps(db: &Connection, start: u64, end: u64, ignored: Option<u64>) -> Result<bool> {
+    db.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM work
+            WHERE time_start < ?2 AND time_start + duration > ?1 AND time_start IS NOT ?3
+        )",
+        (start, end, ignored),
+        |row| row.get(0),
+    )
+}
+
+pub struct ActiveSession {
+    pub session_start: u64,
+    pub partial_start: u64,
+    pub pause_start: Option<u64>,
+    pub session_paused: u64,
+    pub partial_paused: u64,
+    pub completed_sessions: u32,
+    pub project_id: Option<usize>,
+    pub task_id: Option<usize>,
+    pub heartbeat: u64,
+    pub headless: bool,
+}
+
+pub fn save_active_session(db: &Connection, session: &ActiveSession) -> Result<usize> {
+    // there is only ever one active session, always stored with id 1
+    db.execute(
+        "INSERT OR REPLACE INTO active_session (
+            id,
+            session_start,
+            partial_start,
+            pause_start,
+            session_paused,
+            partial_paused,
+            completed_sessions,
+            project_id,
+            task_id,
+            heartbeat,
+            headless
+        ) VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        (
+            session.session_start,
+            session.partial_start,
+            session.pause_start,
+            session.session_paused,
+            session.partial_paused,
+            session.completed_sessions,
+            session.project_id,
+            session.task_id,
+            session.heartbeat,
+            session.headless,
+        ),
+    )
+}
+
+pub fn get_active_session(db: &Connection) -> Result<Option<ActiveSession>> {
+    db.query_row(
+        "SELECT
+            session_start,
+            partial_start,
+            pause_start,
+            session_paused,
+            partial_paused,
+            completed_sessions,
+            project_id,
+            task_id,
+            heartbeat,
+            headless
+        FROM
+            active_session
+        WHERE
+            id = 1",
+        (),
+        |row| {
+            Ok(ActiveSession {
+                session_start: row.get(0)?,
+                partial_start: row.get(1)?,
+                pause_start: row.get(2)?,
+                session_paused: row.get(3)?,
+                partial_paused: row.get(4)?,
+                completed_sessions: row.get(5)?,
+                project_id: row.get(6)?,
+                task_id: row.get(7)?,
+                heartbeat: row.get(8)?,
+                headless: row.get(9)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn delete_active_session(db: &Connection) -> Result<usize> {
+    db.execute("DELETE FROM active_session", ())
+}
+
+// the day is considered to end config_offset_hours after midnight
+pub fn day_start(day: &NaiveDate, config_offset_hours: u32) -> DateTime<Local> {
     //let local_offset = Local::now().offset().clone();
-    let day_start = day
-        .and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap())
+    day.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap())
         .and_local_timezone(Local::now().timezone())
         .unwrap()
         .checked_add_signed(TimeDelta::hours((config_offset_hours % 24) as i64))
-        .unwrap();
-    let next_day_start = day_start.checked_add_signed(TimeDelta::hours(24)).unwrap();
+        .unwrap()
+}
+
+pub struct ExportedWorkSession {
+    pub time_start: u64,
+    pub duration: u64,
+    pub project_id: Option<usize>,
+    // names from the top level project down, joined with " / "
+    pub project_path: Option<String>,
+    pub archived: bool,
+}
+
+pub fn get_work_sessions_for_export(
+    db: &Connection,
+    start: i64,
+    end: i64,
+) -> Result<Vec<ExportedWorkSession>> {
+    let mut stmt = db.prepare(
+        "WITH RECURSIVE project_paths AS (
+            SELECT
+                id,
+                name AS path
+            FROM
+                projects
+            WHERE
+                parent IS NULL
+
+            UNION ALL
+
+            SELECT
+                p.id,
+                pp.path || ' / ' || p.name
+            FROM
+                projects p
+            INNER JOIN
+                project_paths pp ON p.parent = pp.id
+        )
+
+        SELECT
+            w.time_start,
+            w.duration,
+            w.project_id,
+            pp.path,
+            COALESCE(p.archived, 0)
+        FROM
+            work w
+        LEFT JOIN
+            projects p ON p.id = w.project_id
+        LEFT JOIN
+            project_paths pp ON pp.id = w.project_id
+        WHERE
+            w.time_start >= ?1 AND w.time_start < ?2
+        ORDER BY
+            w.time_start",
+    )?;
+    stmt.query_map((start, end), |row| {
+        Ok(ExportedWorkSession {
+            time_start: row.get(0)?,
+            duration: row.get(1)?,
+            project_id: row.get(2)?,
+            project_path: row.get(3)?,
+            archived: row.get(4)?,
+        })
+    })?
+    .collect()
+}
+
+pub struct DayWork {
+    pub day: NaiveDate,
+    pub hours: f32,
+    // completed pomodoros
+    pub sessions: u32,
+}
+
+// work per day between start and end in one query, days without work are left out
+pub fn get_work_per_day(
+    db: &Connection,
+    start: i64,
+    end: i64,
+    config_offset_hours: u32,
+) -> Result<Vec<DayWork>> {
+    // shifting the local time back by the offset gives the day a session counts towards
+    let mut stmt = db.prepare(
+        "SELECT
+            date(time_start, 'unixepoch', 'localtime', ?3) AS day,
+            SUM(duration),
+            SUM(completed)
+        FROM
+            work
+        WHERE
+            time_start >= ?1 AND time_start < ?2
+        GROUP BY
+            day",
+    )?;
+    let offset = format!("-{} hours", config_offset_hours % 24);
+    stmt.query_map((start, end, offset), |row| {
+        let day =
+            NaiveDate::parse_from_str(&row.get::<_, String>(0)?, "%Y-%m-%d").map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+        Ok(DayWork {
+            day,
+            hours: row.get::<_, f32>(1)? / (60.0 * 60.0),
+            sessions: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
+pub fn get_work_hours_between(db: &Connection, start: i64, end: i64) -> Result<f32> {
     db.query_row::<Option<f32>, _, _>(
         "SELECT SUM(duration)
         FROM work
         WHERE time_start >= ?1 AND time_start < ?2",
-        (day_start.timestamp(), next_day_start.timestamp()),
+        (start, end),
         |row| row.get(0),
     )
     .map(|secs| secs.unwrap_or(0.0) / (60.0 * 60.0))
 }
 
-pub fn get_tasks(db: &Connection, project_id: Option<usize>) -> Result<Vec<TodoTask>> {
+pub struct GroupHours {
+    // None for sessions on the parent itself or on no project
+    pub project_id: Option<usize>,
+    pub name: Option<String>,
+    pub hours: f32,
+}
+
+// hours per child of parent, or per top-level project when parent is None, with their own
+// sub-projects folded in
+pub fn get_work_hours_by_group(
+    db: &Connection,
+    start: i64,
+    end: i64,
+    parent: Option<usize>,
+) -> Result<Vec<GroupHours>> {
+    let mut stmt = db.prepare(
+        "WITH RECURSIVE lineage(id, group_id) AS (
+            SELECT
+                id,
+                id
+            FROM
+                projects
+            WHERE
+                parent IS ?3
+
+            UNION ALL
+
+            SELECT
+                p.id,
+                l.group_id
+            FROM
+                projects p
+            INNER JOIN
+                lineage l ON p.parent = l.id
+        )
+
+        SELECT
+            l.group_id,
+            p.name,
+            SUM(w.duration)
+        FROM
+            work w
+        LEFT JOIN
+            lineage l ON l.id = w.project_id
+        LEFT JOIN
+            projects p ON p.id = COALESCE(l.group_id, ?3)
+        WHERE
+            w.time_start >= ?1 AND w.time_start < ?2
+            AND (?3 IS NULL OR l.group_id IS NOT NULL OR w.project_id = ?3)
+        GROUP BY
+            l.group_id",
+    )?;
+    stmt.query_map((start, end, parent), |row| {
+        Ok(GroupHours {
+            project_id: row.get(0)?,
+            name: row.get(1)?,
+            hours: row.get::<_, f32>(2)? / (60.0 * 60.0),
+        })
+    })?
+    .collect()
+}
+
+// open tasks first, completed ones only when include_completed is set.
+// Higher priority goes first, then the order the user arranged
+pub fn get_tasks(
+    db: &Connection,
+    project_id: Option<usize>,
+    include_completed: bool,
+) -> Result<Vec<TodoTask>> {
     let mut stmt = db.prepare(
         "SELECT
             id,
-            name
+            name,
+            completed_at,
+            estimate,
+            priority,
+            parent,
+            (SELECT COALESCE(SUM(duration), 0) FROM work WHERE task_id = tasks.id),
+            (SELECT COUNT(*) FROM work WHERE task_id = tasks.id AND completed),
+            due,
+            recurrence
         FROM
             tasks
         WHERE
-            project_id = ?1",
+            project_id = ?1 AND (completed_at IS NULL OR ?2)
+        ORDER BY
+            completed_at IS NOT NULL, priority DESC, position, id",
     )?;
     let mut stmt_null = db.prepare(
         "SELECT
             id,
-            name
+            name,
+            completed_at,
+            estimate,
+            priority,
+            parent,
+            (SELECT COALESCE(SUM(duration), 0) FROM work WHERE task_id = tasks.id),
+            (SELECT COUNT(*) FROM work WHERE task_id = tasks.id AND completed),
+            due,
+            recurrence
         FROM
             tasks
         WHERE
-            project_id IS NULL",
+            project_id IS NULL AND (completed_at IS NULL OR ?1)
+        ORDER BY
+            completed_at IS NOT NULL, priority DESC, position, id",
     )?;
+    let task = |row: &rusqlite::Row| {
+        Ok(TodoTask {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            completed_at: row.get(2)?,
+            estimate: row.get(3)?,
+            priority: Priority::from_value(row.get(4)?),
+            parent: row.get(5)?,
+            children: vec![],
+            worked_secs: row.get(6)?,
+            pomodoros: row.get(7)?,
+            due: row
+                .get::<_, Option<String>>(8)?
+                .and_then(|s| s.parse().ok()),
+            recurrence: row
+                .get::<_, Option<String>>(9)?
+                .and_then(|s| Recurrence::parse(&s)),
+        })
+    };
     if let Some(project_id) = project_id {
-        stmt.query_map((project_id,), |row| {
-            Ok(TodoTask {
-                id: row.get(0)?,
-                name: row.get(1)?,
-            })
-        })?
-        .collect()
-    } else {
-        stmt_null
-            .query_map((), |row| {
-                Ok(TodoTask {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                })
-            })?
+        stmt.query_map((project_id, include_completed), task)?
             .collect()
+    } else {
+        stmt_null.query_map((include_completed,), task)?.collect()
     }
 }
 
-pub fn add_task(db: &Connection, name: String, project_id: Option<usize>) -> Result<usize> {
+// new tasks go to the end of the list
+pub fn add_task(
+    db: &Connection,
+    name: String,
+    project_id: Option<usize>,
+    parent: Option<usize>,
+) -> Result<usize> {
     db.query_row(
-        "INSERT INTO tasks (name, project_id) VALUES (?1, ?2) RETURNING id",
-        (name, project_id),
+        "INSERT INTO tasks (name, project_id, parent, position)
+        VALUES (
+            ?1,
+            ?2,
+            ?3,
+            (SELECT COALESCE(MAX(position), 0) + 1 FROM tasks WHERE project_id IS ?2)
+        )
+        RETURNING id",
+        (name, project_id, parent),
         |row| row.get(0),
     )
 }
@@ -220,6 +697,194 @@ pub fn update_task(db: &Connection, id: usize, name: String) -> Result<usize> {
     )
 }
 
-pub fn delete_task(db: &Connection, id: usize) -> Result<usize> {
-    db.execute("DELETE FROM tasks WHERE id = ?1", (id,))
+pub fn update_task_priority(db: &Connection, id: usize, priority: Priority) -> Result<usize> {
+    db.execute(
+        "UPDATE tasks
+        SET priority = ?2
+        WHERE id = ?1",
+        (id, priority.value()),
+    )
+}
+
+// positions follow the order of ids
+pub fn set_task_positions(db: &Connection, ids: &[usize]) -> Result<()> {
+    let tx = db.unchecked_transaction()?;
+    for (position, id) in ids.iter().enumerate() {
+        tx.execute(
+            "UPDATE tasks
+            SET position = ?2
+            WHERE id = ?1",
+            (id, position),
+        )?;
+    }
+    tx.commit()
+}
+
+pub fn update_task_estimate(db: &Connection, id: usize, estimate: Option<u32>) -> Result<usize> {
+    db.execute(
+        "UPDATE tasks
+        SET estimate = ?2
+        WHERE id = ?1",
+        (id, estimate),
+    )
+}
+
+pub fn update_task_due(db: &Connection, id: usize, due: Option<NaiveDate>) -> Result<usize> {
+    db.execute(
+        "UPDATE tasks
+        SET due = ?2
+        WHERE id = ?1",
+        (id, due.map(|d| d.to_string())),
+    )
+}
+
+pub fn update_task_recurrence(
+    db: &Connection,
+    id: usize,
+    recurrence: Option<Recurrence>,
+) -> Result<usize> {
+    db.execute(
+        "UPDATE tasks
+        SET recurrence = ?2
+        WHERE id = ?1",
+        (id, recurrence.map(|r| r.as_string())),
+    )
+}
+
+// adds the task again as an open one due on due, next to the original
+pub fn repeat_task(db: &Connection, id: usize, due: NaiveDate) -> Result<usize> {
+    db.query_row(
+        "INSERT INTO tasks
+            (name, project_id, parent, estimate, priority, position, recurrence, due, repeat_of)
+        SELECT name, project_id, parent, estimate, priority, position, recurrence, ?2, id
+        FROM tasks
+        WHERE id = ?1
+        RETURNING id",
+        (id, due.to_string()),
+        |row| row.get(0),
+    )
+}
+
+// Deletes the open task repeating the task and hands its recurrence back, unless it has
+// sessions or sub-tasks already
+pub fn unrepeat_task(db: &Connection, id: usize) -> Result<()> {
+    let tx = db.unchecked_transaction()?;
+    let repeat: Option<(usize, Option<String>)> = tx
+        .query_row(
+            "SELECT id, recurrence
+            FROM tasks t
+            WHERE repeat_of = ?1
+                AND completed_at IS NULL
+                AND NOT EXISTS (SELECT 1 FROM work WHERE task_id = t.id)
+                AND NOT EXISTS (SELECT 1 FROM tasks WHERE parent = t.id)",
+            (id,),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    if let Some((repeat_id, recurrence)) = repeat {
+        tx.execute("DELETE FROM tasks WHERE id = ?1", (repeat_id,))?;
+        tx.execute(
+            "UPDATE tasks
+            SET recurrence = ?2
+            WHERE id = ?1",
+            (id, recurrence),
+        )?;
+    }
+    tx.commit()
+}
+
+// Deletes the task with its sub-tasks at any depth, the work recorded on them stays without a
+// task. Returns the ids of the deleted tasks
+pub fn delete_task(db: &Connection, id: usize) -> Result<Vec<usize>> {
+    let tx = db.unchecked_transaction()?;
+    let ids: Vec<usize> = tx
+        .prepare(
+            "WITH RECURSIVE subtasks AS (
+                SELECT ?1 AS id
+                UNION ALL
+                SELECT t.id FROM tasks t JOIN subtasks s ON t.parent = s.id
+            )
+            SELECT id FROM subtasks",
+        )?
+        .query_map((id,), |row| row.get(0))?
+        .collect::<Result<_>>()?;
+    // sub-tasks go before their parents
+    for id in ids.iter().rev() {
+        tx.execute("UPDATE work SET task_id = NULL WHERE task_id = ?1", (id,))?;
+        tx.execute(
+            "UPDATE active_session SET task_id = NULL WHERE task_id = ?1",
+            (id,),
+        )?;
+        tx.execute(
+            "UPDATE tasks SET repeat_of = NULL WHERE repeat_of = ?1",
+            (id,),
+        )?;
+        tx.execute("DELETE FROM tasks WHERE id = ?1", (id,))?;
+    }
+    tx.commit()?;
+    Ok(ids)
+}
+
+// open sub-tasks of the task at any depth
+pub fn get_open_subtasks(db: &Connection, id: usize) -> Result<Vec<usize>> {
+    let mut stmt = db.prepare(
+        "WITH RECURSIVE subtasks AS (
+            SELECT id, completed_at FROM tasks WHERE parent = ?1
+            UNION ALL
+            SELECT t.id, t.completed_at FROM tasks t JOIN subtasks s ON t.parent = s.id
+        )
+        SELECT id FROM subtasks WHERE completed_at IS NULL",
+    )?;
+    stmt.query_map((id,), |row| row.get(0))?.collect()
+}
+
+// None reopens the task
+pub fn set_task_completed(db: &Connection, id: usize, completed_at: Option<u64>) -> Result<usize> {
+    db.execute(
+        "UPDATE tasks
+        SET completed_at = ?2
+        WHERE id = ?1",
+        (id, completed_at),
+    )
+}
+
+pub struct CompletedTask {
+    pub name: String,
+    pub project_id: Option<usize>,
+    pub project_name: Option<String>,
+    pub completed_at: u64,
+    pub estimate: Option<u32>,
+    // completed pomodoros recorded on the task
+    pub pomodoros: u32,
+}
+
+// tasks completed between start and end, oldest first
+pub fn get_completed_tasks(db: &Connection, start: i64, end: i64) -> Result<Vec<CompletedTask>> {
+    let mut stmt = db.prepare(
+        "SELECT
+            tasks.name,
+            tasks.project_id,
+            projects.name,
+            tasks.completed_at,
+            tasks.estimate,
+            (SELECT COUNT(*) FROM work WHERE task_id = tasks.id AND completed)
+        FROM
+            tasks
+            LEFT JOIN projects ON tasks.project_id = projects.id
+        WHERE
+            tasks.completed_at >= ?1 AND tasks.completed_at < ?2 AND TRIM(tasks.name) != ''
+        ORDER BY
+            tasks.completed_at",
+    )?;
+    stmt.query_map((start, end), |row| {
+        Ok(CompletedTask {
+            name: row.get(0)?,
+            project_id: row.get(1)?,
+            project_name: row.get(2)?,
+            completed_at: row.get(3)?,
+            estimate: row.get(4)?,
+            pomodoros: row.get(5)?,
+        })
+    })?
+    .collect()
 }
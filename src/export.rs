@@ -0,0 +1,89 @@
+use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::db;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Session {
+    // local time in RFC 3339
+    start: String,
+    duration_seconds: u64,
+    project_id: Option<usize>,
+    project: Option<String>,
+    archived: bool,
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+// writes the sessions of the days from..=to, returns how many were written
+pub fn export_sessions(
+    conn: &Connection,
+    from: NaiveDate,
+    to: NaiveDate,
+    config_offset_hours: u32,
+    format: Format,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let start = db::day_start(&from, config_offset_hours);
+    let end = db::day_start(&to.succ_opt().unwrap(), config_offset_hours);
+    let sessions: Vec<Session> =
+        db::get_work_sessions_for_export(conn, start.timestamp(), end.timestamp())
+            .map_err(io::Error::other)?
+            .into_iter()
+            .map(|s| Session {
+                start: DateTime::from_timestamp(s.time_start as i64, 0)
+                    .unwrap_or_default()
+                    .with_timezone(&Local)
+                    .to_rfc3339(),
+                duration_seconds: s.duration,
+                project_id: s.project_id,
+                project: s.project_path,
+                archived: s.archived,
+            })
+            .collect();
+
+    match format {
+        Format::Csv => {
+            writeln!(out, "start,duration_seconds,project_id,project,archived")?;
+            for s in &sessions {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    s.start,
+                    s.duration_seconds,
+                    s.project_id.map(|id| id.to_string()).unwrap_or_default(),
+                    csv_field(s.project.as_deref().unwrap_or_default()),
+                    s.archived
+                )?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &sessions)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(sessions.len())
+}
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+use rusqlite::Connection;
+
+use crate::{
+    db,
+    util::{overlaps_any, session_day},
+};
+
+// the session being corrected, duration is kept as typed in minutes until saved
+pub struct EditedSession {
+    pub time_start: u64,
+    pub project_id: Option<usize>,
+    pub duration: String,
+}
+
+// A struct for caching the recorded sessions of the week shown in the history tab
+#[derive(Default)]
+pub struct History {
+    sessions: Vec<db::ExportedWorkSession>,
+    weeks_back: u32,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    edited: Option<EditedSession>,
+}
+
+fn parse_minutes(minutes: &str) -> Result<u64, String> {
+    minutes
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|m| *m > 0.0)
+        .map(|m| (m * 60.0).round() as u64)
+        .ok_or_else(|| "Duration should be a positive number of minutes".to_owned())
+}
+
+impl History {
+    pub fn fetch(&mut self, conn: &Connection, config_offset_hours: u32) {
+        let today = session_day(Local::now().timestamp() as u64, config_offset_hours);
+        self.last_day = today - TimeDelta::weeks(self.weeks_back as i64);
+        self.first_day = self.last_day - TimeDelta::days(6);
+        let start = db::day_start(&self.first_day, config_offset_hours);
+        let end = db::day_start(&self.last_day.succ_opt().unwrap(), config_offset_hours);
+        self.sessions = db::get_work_sessions_for_export(conn, start.timestamp(), end.timestamp())
+            .expect("Failed to fetch work sessions");
+        self.edited = None;
+    }
+    // newest day first, only days with sessions
+    pub fn get_by_day(
+        &self,
+        config_offset_hours: u32,
+    ) -> Vec<(NaiveDate, Vec<&db::ExportedWorkSession>)> {
+        let mut days: Vec<(NaiveDate, Vec<&db::ExportedWorkSession>)> = vec![];
+        for session in self.sessions.iter().rev() {
+            let day = session_day(session.time_start, config_offset_hours);
+            match days.last_mut() {
+                Some((last_day, sessions)) if *last_day == day => sessions.push(session),
+                _ => days.push((day, vec![session])),
+            }
+        }
+        days
+    }
+    // first and last day shown
+    pub fn get_days(&self) -> (NaiveDate, NaiveDate) {
+        (self.first_day, self.last_day)
+    }
+    pub fn get_weeks_back(&self) -> u32 {
+        self.weeks_back
+    }
+    pub fn set_weeks_back(&mut self, weeks_back: u32, conn: &Connection, config_offset_hours: u32) {
+        self.weeks_back = weeks_back;
+        self.fetch(conn, config_offset_hours);
+    }
+    pub fn initiate_edit(&mut self, time_start: u64) {
+        self.edited = self
+            .sessions
+            .iter()
+            .find(|s| s.time_start == time_start)
+            .map(|s| EditedSession {
+                time_start: s.time_start,
+                project_id: s.project_id,
+                duration: ((s.duration as f64 / 60.0 * 10.0).round() / 10.0).to_string(),
+            });
+    }
+    pub fn get_edited(&self) -> Option<&EditedSession> {
+        self.edited.as_ref()
+    }
+    pub fn set_edited_project(&mut self, project_id: usize) {
+        if let Some(edited) = self.edited.as_mut() {
+            edited.project_id = Some(project_id);
+        }
+    }
+    pub fn set_edited_duration(&mut self, duration: String) {
+        if let Some(edited) = self.edited.as_mut() {
+            edited.duration = duration;
+        }
+    }
+    // Keeps the edit open when the new duration can't be saved. unrecorded is the work of the
+    // timer that is not in the DB yet, see Pomo::unrecorded_work
+    pub fn finish_edit(
+        &mut self,
+        conn: &Connection,
+        unrecorded: &[(u64, u64)],
+        config_offset_hours: u32,
+    ) -> Result<(), String> {
+        if let Some(edited) = self.edited.as_ref() {
+            let duration = parse_minutes(&edited.duration)?;
+            let end = edited.time_start + duration;
+            if db::work_overlaps(conn, edited.time_start, end, Some(edited.time_start))
+                .map_err(|e| e.to_string())?
+            {
+                return Err("That would overlap the next session".to_owned());
+            }
+            if overlaps_any(edited.time_start, end, unrecorded) {
+                return Err("That would overlap the running session".to_owned());
+            }
+            db::update_work_session(conn, edited.time_start, duration, edited.project_id)
+                .map_err(|e| e.to_string())?;
+        }
+        self.fetch(conn, config_offset_hours);
+        Ok(())
+    }
+    pub fn delete_edited(&mut self, conn: &Connection, config_offset_hours: u32) {
+        if let Some(edited) = self.edited.as_ref() {
+            db::delete_work_session(conn, edited.time_start)
+                .expect("Failed to delete work session");
+        }
+        self.fetch(conn, config_offset_hours);
+    }
+    // records work done away from the timer, unrecorded as in finish_edit
+    pub fn add(
+        &mut self,
+        conn: &Connection,
+        start: DateTime<Local>,
+        minutes: &str,
+        project_id: Option<usize>,
+        unrecorded: &[(u64, u64)],
+        config_offset_hours: u32,
+    ) -> Result<(), String> {
+        let duration = parse_minutes(minutes)?;
+        let time_start = start.timestamp().max(0) as u64;
+        let end = time_start + duration;
+        if end > Local::now().timestamp() as u64 {
+            return Err("Manual entries can't end in the future".to_owned());
+        }
+        if db::work_overlaps(conn, time_start, end, None).map_err(|e| e.to_string())? {
+            return Err("That would overlap a recorded session".to_owned());
+        }
+        if overlaps_any(time_start, end, unrecorded) {
+            return Err("That would overlap the running session".to_owned());
+        }
+        db::add_work_session(
+            conn,
+            &db::WorkSession {
+                time_start,
+                duration,
+                project_id,
+                task_id: None,
+                completed: false,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        self.fetch(conn, config_offset_hours);
+        Ok(())
+    }
+}
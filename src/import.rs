@@ -0,0 +1,431 @@
+// Imports time entries from the CSV exports of Toggl Track and Clockify
+use chrono::{Local, NaiveDate, NaiveTime};
+use rusqlite::Connection;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::{db, util};
+
+pub struct ImportedEntry {
+    pub time_start: u64,
+    pub duration: u64,
+    pub client: Option<String>,
+    pub project: Option<String>,
+}
+
+pub struct Preview {
+    // sorted by start time, none of them overlapping
+    pub entries: Vec<ImportedEntry>,
+    // "Client / Project" paths that do not exist yet
+    pub new_projects: Vec<String>,
+    pub overlapping: usize,
+    pub unreadable: usize,
+}
+
+impl Preview {
+    pub fn total_hours(&self) -> f32 {
+        self.entries.iter().map(|e| e.duration).sum::<u64>() as f32 / (60.0 * 60.0)
+    }
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} sessions, {:.1} hours",
+            self.entries.len(),
+            self.total_hours()
+        );
+        if !self.new_projects.is_empty() {
+            summary += &format!("\nNew projects: {}", self.new_projects.join(", "));
+        }
+        if self.overlapping > 0 {
+            summary += &format!("\nSkipping {} overlapping sessions", self.overlapping);
+        }
+        if self.unreadable > 0 {
+            summary += &format!("\nSkipping {} unreadable rows", self.unreadable);
+        }
+        summary
+    }
+}
+
+// handles quoted fields with commas, escaped quotes and line breaks
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+// Clockify follows the user's date format setting, US style being the default
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%m/%d/%Y", "%d/%m/%Y", "%d.%m.%Y", "%d-%m-%Y"];
+
+// The format reading the most of the dates, the same for the whole file. 03/04/2025 could be
+// either slash format, so a file with nothing but such dates is refused rather than guessed
+fn date_format(dates: &[String]) -> Result<&'static str, String> {
+    let readable = |format: &&str| {
+        dates
+            .iter()
+            .filter(|d| NaiveDate::parse_from_str(d, format).is_ok())
+            .count()
+    };
+    let most = DATE_FORMATS.iter().map(readable).max().unwrap_or_default();
+    let best: Vec<&str> = DATE_FORMATS
+        .into_iter()
+        .filter(|f| readable(f) == most)
+        .collect();
+    match best.as_slice() {
+        [format] => Ok(format),
+        _ if most > 0 => Err(
+            "The dates could be month/day or day/month, export again with the date format set \
+            to year-month-day"
+                .to_owned(),
+        ),
+        _ => Ok(DATE_FORMATS[0]),
+    }
+}
+
+fn parse_date(s: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, format).ok()
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"]
+        .iter()
+        .find_map(|f| NaiveTime::parse_from_str(s, f).ok())
+}
+
+// "1:25:00" or decimal hours like "1.42"
+fn parse_duration(s: &str) -> Option<u64> {
+    let parts: Vec<&str> = s.split(':').collect();
+    match parts.as_slice() {
+        [h, m, sec] => Some(
+            h.parse::<u64>().ok()? * 3600
+                + m.parse::<u64>().ok()? * 60
+                + sec.parse::<u64>().ok()?,
+        ),
+        [hours] => hours
+            .parse::<f64>()
+            .ok()
+            .map(|h| (h * 3600.0).round() as u64),
+        _ => None,
+    }
+}
+
+struct Columns {
+    project: Option<usize>,
+    client: Option<usize>,
+    start_date: usize,
+    start_time: usize,
+    duration: usize,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> Option<Self> {
+        let find = |names: &[&str]| {
+            header
+                .iter()
+                .position(|h| names.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+        };
+        Some(Columns {
+            project: find(&["Project"]),
+            client: find(&["Client"]),
+            start_date: find(&["Start date"])?,
+            start_time: find(&["Start time"])?,
+            // Toggl, then the two Clockify variants
+            duration: find(&["Duration", "Duration (h)", "Duration (decimal)"])?,
+        })
+    }
+}
+
+fn project_path(client: &Option<String>, project: &Option<String>) -> Option<String> {
+    match (client, project) {
+        (Some(client), Some(project)) => Some(format!("{client} / {project}")),
+        (None, Some(project)) => Some(project.clone()),
+        (Some(client), None) => Some(client.clone()),
+        (None, None) => None,
+    }
+}
+
+// the client becomes a top level project with the project under it
+fn resolve_project(
+    conn: &Connection,
+    client: &Option<String>,
+    project: &Option<String>,
+    create: bool,
+) -> rusqlite::Result<Option<usize>> {
+    let mut parent = None;
+    for name in [client, project].into_iter().flatten() {
+        parent = match db::find_project(conn, name, parent)? {
+            Some(id) => Some(id),
+            None if create => Some(db::add_project(conn, name, parent)?),
+            None => return Ok(None),
+        };
+    }
+    Ok(parent)
+}
+
+// unrecorded as in apply
+pub fn preview(
+    conn: &Connection,
+    path: &Path,
+    unrecorded: &[(u64, u64)],
+) -> Result<Preview, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let rows = parse_csv(&text);
+    let (header, rows) = rows.split_first().ok_or("The file is empty")?;
+    let columns = Columns::from_header(header).ok_or(
+        "Expected Start date, Start time and Duration columns, like Toggl and Clockify exports",
+    )?;
+
+    let mut preview = Preview {
+        entries: vec![],
+        new_projects: vec![],
+        overlapping: 0,
+        unreadable: 0,
+    };
+    let rows: Vec<&Vec<String>> = rows
+        .iter()
+        .filter(|r| r.iter().any(|f| !f.trim().is_empty()))
+        .collect();
+    let dates: Vec<String> = rows
+        .iter()
+        .filter_map(|r| r.get(columns.start_date))
+        .map(|d| d.trim().to_owned())
+        .collect();
+    let date_format = date_format(&dates)?;
+    let mut candidates = vec![];
+    for row in rows {
+        let field = |i: Option<usize>| {
+            i.and_then(|i| row.get(i))
+                .map(|f| f.trim().to_owned())
+                .filter(|f| !f.is_empty())
+        };
+        let time_start = field(Some(columns.start_date))
+            .and_then(|d| parse_date(&d, date_format))
+            .zip(field(Some(columns.start_time)).and_then(|t| parse_time(&t)))
+            .and_then(|(d, t)| d.and_time(t).and_local_timezone(Local).earliest());
+        let duration = field(Some(columns.duration)).and_then(|d| parse_duration(&d));
+        match (time_start, duration) {
+            (Some(time_start), Some(duration)) if duration > 0 => candidates.push(ImportedEntry {
+                time_start: time_start.timestamp() as u64,
+                duration,
+                client: field(columns.client),
+                project: field(columns.project),
+            }),
+            _ => preview.unreadable += 1,
+        }
+    }
+
+    candidates.sort_by_key(|e| e.time_start);
+    let mut new_projects = BTreeSet::new();
+    for entry in candidates {
+        let end = entry.time_start + entry.duration;
+        let overlaps_previous = preview
+            .entries
+            .last()
+            .is_some_and(|last| last.time_start + last.duration > entry.time_start);
+        if overlaps_previous
+            || db::work_overlaps(conn, entry.time_start, end, None).map_err(|e| e.to_string())?
+            || util::overlaps_any(entry.time_start, end, unrecorded)
+        {
+            preview.overlapping += 1;
+            continue;
+        }
+        let exists = resolve_project(conn, &entry.client, &entry.project, false)
+            .map_err(|e| e.to_string())?
+            .is_some();
+        if let Some(path) = project_path(&entry.client, &entry.project).filter(|_| !exists) {
+            new_projects.insert(path);
+        }
+        preview.entries.push(entry);
+    }
+    preview.new_projects = new_projects.into_iter().collect();
+    Ok(preview)
+}
+
+// Returns the number of sessions recorded. Overlaps are checked again, as sessions may have
+// been recorded since the preview, unrecorded is the work of the timer not in the DB yet
+pub fn apply(
+    conn: &Connection,
+    preview: &Preview,
+    unrecorded: &[(u64, u64)],
+) -> rusqlite::Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut count = 0;
+    for entry in &preview.entries {
+        let end = entry.time_start + entry.duration;
+        if db::work_overlaps(&tx, entry.time_start, end, None)?
+            || util::overlaps_any(entry.time_start, end, unrecorded)
+        {
+            continue;
+        }
+        let project_id = resolve_project(&tx, &entry.client, &entry.project, true)?;
+        db::add_work_session(
+            &tx,
+            &db::WorkSession {
+                time_start: entry.time_start,
+                duration: entry.duration,
+                project_id,
+                task_id: None,
+                completed: false,
+            },
+        )?;
+        count += 1;
+    }
+    tx.commit()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    const TOGGL: &str = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags,Amount ()
+Ann,ann@example.com,Acme,Website,,\"Header, footer\",No,2025-03-04,09:00:00,2025-03-04,10:30:00,01:30:00,,
+Ann,ann@example.com,,Reading,,,No,2025-03-04,10:00:00,2025-03-04,10:45:00,00:45:00,,
+Ann,ann@example.com,Acme,Website,,,No,2025-03-05,14:00:00,2025-03-05,14:25:00,00:25:00,,
+";
+
+    const CLOCKIFY: &str = "\u{feff}Project,Client,Description,Task,User,Group,Email,Tags,Billable,Start Date,Start Time,End Date,End Time,Duration (h),Duration (decimal)
+Website,Acme,,,Ann,,ann@example.com,,No,03/04/2025,09:00:00 AM,03/04/2025,10:30:00 AM,01:30:00,1.50
+Website,Acme,,,Ann,,ann@example.com,,No,03/25/2025,01:00:00 PM,03/25/2025,01:15:00 PM,00:15:00,0.25
+not a date,,,,,,,,,,,,,,
+";
+
+    fn db() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn, None).unwrap();
+        conn
+    }
+
+    fn preview_of(conn: &Connection, name: &str, csv: &str, unrecorded: &[(u64, u64)]) -> Preview {
+        let path = std::env::temp_dir().join(format!(
+            "hellowork_import_test_{name}_{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(&path, csv).unwrap();
+        let preview = preview(conn, &path, unrecorded);
+        std::fs::remove_file(&path).unwrap();
+        preview.unwrap()
+    }
+
+    fn unix(date: &str, time: &str) -> u64 {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+            .and_local_timezone(Local)
+            .unwrap()
+            .timestamp() as u64
+    }
+
+    #[test]
+    fn parses_quoted_csv_fields() {
+        let rows = parse_csv("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",x\n");
+        assert_eq!(rows[0], ["a", "b, c", "say \"hi\""]);
+        assert_eq!(rows[1], ["two\nlines", "x"]);
+    }
+
+    #[test]
+    fn picks_one_date_format_per_file() {
+        let dates = |ds: &[&str]| ds.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        assert_eq!(date_format(&dates(&["2025-03-04"])), Ok("%Y-%m-%d"));
+        // a day past 12 settles it for the whole file
+        assert_eq!(
+            date_format(&dates(&["03/04/2025", "03/25/2025"])),
+            Ok("%m/%d/%Y")
+        );
+        assert_eq!(
+            date_format(&dates(&["03/04/2025", "25/03/2025"])),
+            Ok("%d/%m/%Y")
+        );
+        assert!(date_format(&dates(&["03/04/2025", "04/05/2025"])).is_err());
+        assert_eq!(date_format(&dates(&["04.03.2025"])), Ok("%d.%m.%Y"));
+        assert_eq!(
+            parse_date("03/04/2025", "%d/%m/%Y"),
+            NaiveDate::from_ymd_opt(2025, 4, 3)
+        );
+    }
+
+    #[test]
+    fn parses_times_and_durations() {
+        assert_eq!(parse_time("09:05:30"), NaiveTime::from_hms_opt(9, 5, 30));
+        assert_eq!(parse_time("01:00:00 PM"), NaiveTime::from_hms_opt(13, 0, 0));
+        assert_eq!(parse_time("12:15 AM"), NaiveTime::from_hms_opt(0, 15, 0));
+        assert_eq!(parse_time("noon"), None);
+        assert_eq!(parse_duration("01:30:00"), Some(5400));
+        assert_eq!(parse_duration("26:00:05"), Some(93605));
+        assert_eq!(parse_duration("0.25"), Some(900));
+        assert_eq!(parse_duration("1:30"), None);
+    }
+
+    #[test]
+    fn previews_toggl_export_and_skips_overlaps() {
+        let conn = db();
+        let preview = preview_of(&conn, "toggl", TOGGL, &[]);
+        // the second entry starts within the first one
+        assert_eq!(preview.entries.len(), 2);
+        assert_eq!(preview.overlapping, 1);
+        assert_eq!(preview.unreadable, 0);
+        assert_eq!(preview.entries[0].time_start, unix("2025-03-04", "09:00"));
+        assert_eq!(preview.entries[0].duration, 5400);
+        assert_eq!(preview.entries[0].client.as_deref(), Some("Acme"));
+        assert_eq!(preview.new_projects, ["Acme / Website"]);
+
+        assert_eq!(apply(&conn, &preview, &[]).unwrap(), 2);
+        let projects = db::get_projects(&conn).unwrap();
+        let acme = projects.iter().find(|p| p.name == "Acme").unwrap();
+        let website = projects.iter().find(|p| p.name == "Website").unwrap();
+        assert_eq!(website.parent, Some(acme.id));
+        assert!((acme.total_hours - 1.5 - 25.0 / 60.0).abs() < 0.001);
+
+        // importing again finds everything recorded already
+        let again = preview_of(&conn, "toggl_again", TOGGL, &[]);
+        assert!(again.entries.is_empty());
+        assert_eq!(again.overlapping, 3);
+    }
+
+    #[test]
+    fn previews_clockify_export() {
+        let conn = db();
+        let preview = preview_of(&conn, "clockify", CLOCKIFY, &[]);
+        assert_eq!(preview.entries.len(), 2);
+        assert_eq!(preview.unreadable, 1);
+        assert_eq!(preview.entries[0].time_start, unix("2025-03-04", "09:00"));
+        assert_eq!(preview.entries[1].time_start, unix("2025-03-25", "13:00"));
+        assert_eq!(preview.entries[1].duration, 900);
+        assert_eq!(preview.entries[1].project.as_deref(), Some("Website"));
+    }
+
+    #[test]
+    fn skips_entries_overlapping_the_running_session() {
+        let conn = db();
+        let running = [(unix("2025-03-05", "14:10"), u64::MAX)];
+        let preview = preview_of(&conn, "running", TOGGL, &running);
+        assert_eq!(preview.entries.len(), 1);
+        assert_eq!(preview.overlapping, 2);
+
+        // work the timer started after the preview is checked again
+        let preview = preview_of(&conn, "running_later", TOGGL, &[]);
+        assert_eq!(apply(&conn, &preview, &running).unwrap(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+// Control socket for the running app, one JSON request per line and one JSON response per line
+use directories::ProjectDirs;
+use iced::futures::{Stream, channel::mpsc as futures_mpsc};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+use crate::config;
+use crate::control::{Request, Response};
+
+pub fn socket_path() -> PathBuf {
+    ProjectDirs::from("moe", "msg", "Hello Work")
+        .and_then(|dirs| dirs.runtime_dir().map(|d| d.to_owned()))
+        .filter(|dir| dir.exists() || std::fs::create_dir_all(dir).is_ok())
+        .unwrap_or_else(config::config_dir)
+        .join("hellowork.sock")
+}
+
+// Sends the response back to the connection the request came from
+#[derive(Debug, Clone)]
+pub struct Responder(Arc<Mutex<Option<mpsc::Sender<Response>>>>);
+
+impl Responder {
+    pub fn respond(&self, response: Response) {
+        if let Some(tx) = self.0.lock().unwrap().take() {
+            let _ = tx.send(response);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Incoming {
+    pub request: Request,
+    pub responder: Responder,
+}
+
+// Used as a subscription, yields requests until the app quits
+pub fn listen() -> impl Stream<Item = Incoming> {
+    let (tx, rx) = futures_mpsc::unbounded();
+    std::thread::spawn(move || {
+        if let Some(listener) = bind() {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                std::thread::spawn(move || serve(stream, tx));
+            }
+        }
+    });
+    rx
+}
+
+fn bind() -> Option<UnixListener> {
+    let path = socket_path();
+    match UnixListener::bind(&path) {
+        Ok(listener) => Some(listener),
+        Err(_) if UnixStream::connect(&path).is_ok() => {
+            eprintln!("Another instance is listening on {}", path.display());
+            None
+        }
+        Err(_) => {
+            // left behind by an instance that did not exit cleanly
+            let _ = std::fs::remove_file(&path);
+            UnixListener::bind(&path)
+                .inspect_err(|e| eprintln!("Cannot listen on {}: {e}", path.display()))
+                .ok()
+        }
+    }
+}
+
+fn serve(stream: UnixStream, tx: futures_mpsc::UnboundedSender<Incoming>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (response_tx, response_rx) = mpsc::channel();
+                let responder = Responder(Arc::new(Mutex::new(Some(response_tx))));
+                if tx.unbounded_send(Incoming { request, responder }).is_err() {
+                    return;
+                }
+                response_rx
+                    .recv_timeout(Duration::from_secs(5))
+                    .unwrap_or(Response::new(Err("No response from app".to_owned()), None))
+            }
+            Err(e) => Response::new(Err(format!("Invalid request: {e}")), None),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        if writeln!(writer, "{json}").is_err() {
+            return;
+        }
+    }
+}
+
+// None when the app is not running, so the caller can work on the DB itself
+pub fn send(request: &Request) -> Option<Result<Response, String>> {
+    let mut stream = UnixStream::connect(socket_path()).ok()?;
+    let result = (|| {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(request).unwrap();
+        writeln!(stream, "{json}").map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        BufReader::new(&stream)
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_str(&line).map_err(|e| e.to_string())
+    })();
+    Some(result)
+}
@@ -1,11 +1,21 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod audio;
+mod charts;
+mod cli;
 mod color_schemes;
 mod config;
+mod control;
 mod db;
+mod export;
+mod history;
+mod import;
+#[cfg(unix)]
+mod ipc;
+mod migrations;
 mod pomo;
 mod projects;
+mod report;
 mod stats;
 mod todo_tasks;
 mod util;
@@ -16,19 +26,24 @@ use iced::{
     theme::{Custom, Palette},
     time,
     widget::{
-        MouseArea, Space, button, center, center_x, checkbox, column, container, pick_list, row,
-        scrollable, slider, svg, text, text_input, tooltip,
+        MouseArea, Space, button, center, center_x, checkbox, column, container, pick_list,
+        progress_bar, row, scrollable, slider, svg, text, text_input, tooltip,
     },
     window::{self, Level, Position, Settings},
 };
 use pliced::Chart;
-use plotters::{prelude::*, style::Color};
 use std::iter;
 use std::time::Duration;
 use std::{env, sync::Arc};
 
 use crate::config::WindowGeometry;
 
+const CHART_IMAGE_SIZE: (u32, u32) = (1200, 600);
+const TASK_HANDLE_WIDTH: f32 = 12.0;
+const TASK_PRIORITY_WIDTH: f32 = 24.0;
+const TASK_EXPAND_WIDTH: f32 = 16.0;
+// per level of sub-tasks
+const TASK_INDENT_WIDTH: f32 = 20.0;
 const FONT_SANS: iced::Font = iced::Font::with_name("Lato");
 static HELLO_WORK_ICON: &[u8] = include_bytes!("../img/hello_work_pixel.png");
 
@@ -54,6 +69,13 @@ fn svg_style_primary(
     }
 }
 
+// the scheme's error_color, danger is the colorful one
+fn text_style_error(theme: &Theme) -> text::Style {
+    text::Style {
+        color: Some(theme.palette().warning),
+    }
+}
+
 fn button_style_transparent(_theme: &Theme, _status: button::Status) -> button::Style {
     button::Style {
         background: None,
@@ -78,6 +100,17 @@ fn todo_text_input_style(theme: &Theme, _status: text_input::Status) -> text_inp
 }
 
 pub fn main() -> iced::Result {
+    // any argument switches to the command line, macOS passes -psn_* to app bundles though
+    let args: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("-psn_"))
+        .collect();
+    if !args.is_empty() {
+        #[cfg(windows)]
+        cli::attach_parent_console();
+        std::process::exit(cli::run(&args));
+    }
+
     let icon = iced::window::icon::from_file_data(HELLO_WORK_ICON, None).ok();
     let app_obj = App::new();
 
@@ -108,6 +141,7 @@ enum Tab {
     #[default]
     Main,
     Projects,
+    History,
     Stats,
     Settings,
 }
@@ -119,26 +153,108 @@ struct App {
     pomo: pomo::Pomo,
     theme: Theme,
     archive_project_button_confirm: bool,
+    input_edited_target_hours: String,
+    input_edited_budget_hours: String,
     input_session_length: String,
+    input_short_break_length: String,
+    input_long_break_length: String,
+    input_long_break_interval: String,
     input_day_end_offset_hours: String,
+    input_daily_goal: String,
+    daily_goal_unit: config::GoalUnit,
+    input_export_from: String,
+    input_export_to: String,
+    settings_notice: Option<String>,
+    import_preview: Option<import::Preview>,
+    history: history::History,
+    delete_session_button_confirm: bool,
+    input_manual_date: String,
+    input_manual_start: String,
+    input_manual_duration: String,
+    manual_project: Option<usize>,
+    history_notice: Option<String>,
+    stats_view: stats::StatsView,
+    // the project whose children the breakdown chart shows, top-level projects when None
+    stats_breakdown_parent: Option<usize>,
+    input_stats_from: String,
+    input_stats_to: String,
+    stats_notice: Option<String>,
+    // the to-do task being dragged and the one under the cursor
+    dragged_task: Option<usize>,
+    drag_target_task: Option<usize>,
+    // the task a new sub-task is being added to
+    new_subtask_parent: Option<usize>,
+    // the task with open sub-tasks waiting to be checked off
+    complete_task_prompt: Option<usize>,
+    // the task whose delete button was pressed once
+    delete_task_button_confirm: Option<usize>,
+    // the task whose due date and recurrence are being edited
+    scheduled_task: Option<usize>,
+    input_task_due: String,
 }
 
 impl Default for App {
     fn default() -> Self {
         let pomo = pomo::Pomo::default();
         let input_session_length = (pomo.session_length / 60).to_string();
+        let input_short_break_length = pomo.config.get_short_break_length().to_string();
+        let input_long_break_length = pomo.config.get_long_break_length().to_string();
+        let input_long_break_interval = pomo.config.get_long_break_interval().to_string();
         let input_day_end_offset_hours = pomo.config.get_day_end_offset_hours().to_string();
+        let daily_goal = pomo.config.get_daily_goal();
+        let input_daily_goal = daily_goal.map(|g| g.amount.to_string()).unwrap_or_default();
+        let daily_goal_unit = daily_goal.map_or(config::GoalUnit::Hours, |g| g.unit);
+        let today = chrono::Local::now().date_naive();
+        let input_export_from = today.with_day(1).unwrap().to_string();
+        let input_export_to = today.to_string();
+        let manual_project = pomo.projects.get_active();
+        let (stats_from, stats_to) = pomo
+            .config
+            .get_stats_custom_span()
+            .unwrap_or((today.with_day(1).unwrap(), today));
         let mut app = App {
             mini_window: false,
             current_tab: Tab::default(),
             pomo,
             theme: Theme::CatppuccinLatte,
             archive_project_button_confirm: false,
+            input_edited_target_hours: String::new(),
+            input_edited_budget_hours: String::new(),
             input_day_end_offset_hours,
+            input_daily_goal,
+            daily_goal_unit,
             input_session_length,
+            input_short_break_length,
+            input_long_break_length,
+            input_long_break_interval,
+            input_export_from,
+            input_export_to,
+            settings_notice: None,
+            import_preview: None,
+            history: history::History::default(),
+            delete_session_button_confirm: false,
+            input_manual_date: today.to_string(),
+            input_manual_start: String::new(),
+            input_manual_duration: String::new(),
+            manual_project,
+            history_notice: None,
+            stats_view: stats::StatsView::default(),
+            stats_breakdown_parent: None,
+            input_stats_from: stats_from.to_string(),
+            input_stats_to: stats_to.to_string(),
+            stats_notice: None,
+            dragged_task: None,
+            drag_target_task: None,
+            new_subtask_parent: None,
+            complete_task_prompt: None,
+            delete_task_button_confirm: None,
+            scheduled_task: None,
+            input_task_due: String::new(),
         };
         //initialize theme here
         app.update_theme();
+        app.pomo.fetch_streaks();
+        app.pomo.fetch_completed_task_count();
         app
     }
 }
@@ -147,27 +263,108 @@ impl Default for App {
 enum Message {
     Ignore,
     Toggle,
+    PauseToggle,
     Tick,
+    RecoveredSessionResume,
+    RecoveredSessionSavePartial,
+    RecoveredSessionDiscard,
     DragMove,
-    StoreWindowGeometry { event: iced::window::Event },
+    StoreWindowGeometry {
+        event: iced::window::Event,
+    },
     MiniWindowToggle,
     ProjectSelected(usize),
-    NewProject { parent: Option<usize> },
+    NewProject {
+        parent: Option<usize>,
+    },
     EditProjectInitiate(usize),
     EditProjectFinish,
     EditProjectArchive,
     EditProjectNameInput(String),
+    EditProjectTargetInput(String),
+    EditProjectBudgetInput(String),
+    EditProjectBudgetPeriodSelected(projects::BudgetPeriod),
     TabSelected(Tab),
     SessionLengthChanged(String),
+    ShortBreakLengthChanged(String),
+    LongBreakLengthChanged(String),
+    LongBreakIntervalChanged(String),
     ThemeChanged(Option<String>),
     FilePickerWorkEndAudio,
     WorkEndAudioVolumeChanged(f32),
     TodoTasksEnabledConfigChanged(bool),
     SavePartialSessionsConfigChanged(bool),
     DayEndOffsetHoursConfigChanged(String),
-    NewTodoTask { name: String },
-    EditTodoTask { id: usize, name: String },
-    DeleteTodoTask { id: usize },
+    DailyGoalChanged(String),
+    DailyGoalUnitSelected(config::GoalUnit),
+    ExportFromChanged(String),
+    ExportToChanged(String),
+    ExportSessions(export::Format),
+    ImportPickFile,
+    ImportConfirm,
+    ImportCancel,
+    StatsViewSelected(stats::StatsView),
+    StatsBreakdownParentSelected(Option<usize>),
+    StatsRangeSelected(stats::StatsRange),
+    StatsFromChanged(String),
+    StatsToChanged(String),
+    SaveReport(report::Format),
+    SaveChart(charts::ImageFormat),
+    HistoryWeeksBackChanged(u32),
+    EditSessionInitiate(u64),
+    EditSessionProjectSelected(usize),
+    EditSessionDurationInput(String),
+    EditSessionFinish,
+    EditSessionDelete,
+    ManualEntryDateInput(String),
+    ManualEntryStartInput(String),
+    ManualEntryDurationInput(String),
+    ManualEntryProjectSelected(usize),
+    ManualEntryAdd,
+    NewTodoTask {
+        name: String,
+        parent: Option<usize>,
+    },
+    NewSubTaskInitiate(usize),
+    EditTodoTask {
+        id: usize,
+        name: String,
+    },
+    EditTodoTaskEstimate {
+        id: usize,
+        estimate: String,
+    },
+    CompleteTodoTask {
+        id: usize,
+        completed: bool,
+    },
+    CompleteTodoTaskConfirm {
+        id: usize,
+        subtasks: bool,
+    },
+    CompleteTodoTaskCancel,
+    UndoCompleteTodoTask,
+    DeleteTodoTask {
+        id: usize,
+    },
+    TaskCollapseToggled(usize),
+    TaskScheduleToggle(usize),
+    TaskDueInput(String),
+    TaskRecurrenceSelected(Option<todo_tasks::Recurrence>),
+    TaskPriorityCycle {
+        id: usize,
+    },
+    TaskDragStart(usize),
+    TaskDragOver(usize),
+    TaskDrop(usize),
+    TaskDragEnd,
+    MoveSelectedTask {
+        up: bool,
+    },
+    CurrentTaskSelected(Option<usize>),
+    ShowCompletedTasksToggled(bool),
+    #[cfg(unix)]
+    Ipc(ipc::Incoming),
 }
 
 impl App {
@@ -188,8 +385,22 @@ impl App {
                     self.pomo.cancel_session();
                 }
             }
+            Message::PauseToggle => {
+                self.pomo.toggle_pause();
+            }
             Message::Tick => {
                 self.pomo.check_finished();
+                self.pomo.heartbeat();
+                self.pomo.check_day_rollover();
+            }
+            Message::RecoveredSessionResume => {
+                self.pomo.resume_recovered();
+            }
+            Message::RecoveredSessionSavePartial => {
+                self.pomo.save_recovered_partial();
+            }
+            Message::RecoveredSessionDiscard => {
+                self.pomo.discard_recovered();
             }
             Message::DragMove => {
                 return window::get_latest()
@@ -284,22 +495,27 @@ impl App {
                 return action;
             }
             Message::ProjectSelected(id) => {
-                // If a session is active, save progress accrued for the old project
-                if self.pomo.is_running() {
-                    self.pomo.save_partial_session_if_enabled();
-                }
-
-                self.pomo.projects.set_active(Some(id));
-                self.pomo
-                    .config
-                    .set_last_active_project(id, &self.pomo.config_file_path);
-                self.pomo.tasks.switch_project(&self.pomo.db, Some(id));
+                self.pomo.switch_project(id);
             }
             Message::NewProject { parent } => {
                 self.pomo.projects.add(parent, &self.pomo.db);
             }
             Message::EditProjectInitiate(id) => {
                 self.pomo.projects.initiate_edit(Some(id));
+                self.input_edited_target_hours = self
+                    .pomo
+                    .projects
+                    .get_edited()
+                    .and_then(|p| p.target_hours)
+                    .map(|t| t.to_string())
+                    .unwrap_or_default();
+                self.input_edited_budget_hours = self
+                    .pomo
+                    .projects
+                    .get_edited()
+                    .and_then(|p| p.budget_hours)
+                    .map(|b| b.to_string())
+                    .unwrap_or_default();
             }
             Message::EditProjectFinish => {
                 self.pomo.projects.finish_edit(&self.pomo.db);
@@ -308,6 +524,35 @@ impl App {
             Message::EditProjectNameInput(name) => {
                 self.pomo.projects.set_edited_name(name);
             }
+            Message::EditProjectTargetInput(target_hours) => {
+                self.input_edited_target_hours = target_hours;
+                // an empty input removes the target
+                if self.input_edited_target_hours.trim().is_empty() {
+                    self.pomo.projects.set_edited_target_hours(None);
+                } else if let Ok(target_hours) =
+                    self.input_edited_target_hours.trim().parse::<f32>()
+                {
+                    self.pomo
+                        .projects
+                        .set_edited_target_hours(Some(target_hours).filter(|t| *t > 0.0));
+                }
+            }
+            Message::EditProjectBudgetInput(budget_hours) => {
+                self.input_edited_budget_hours = budget_hours;
+                // an empty input removes the budget
+                if self.input_edited_budget_hours.trim().is_empty() {
+                    self.pomo.projects.set_edited_budget_hours(None);
+                } else if let Ok(budget_hours) =
+                    self.input_edited_budget_hours.trim().parse::<f32>()
+                {
+                    self.pomo
+                        .projects
+                        .set_edited_budget_hours(Some(budget_hours).filter(|b| *b > 0.0));
+                }
+            }
+            Message::EditProjectBudgetPeriodSelected(period) => {
+                self.pomo.projects.set_edited_budget_period(period);
+            }
             Message::EditProjectArchive => {
                 if self.archive_project_button_confirm {
                     self.pomo.projects.archive_edited_item(&self.pomo.db);
@@ -317,6 +562,11 @@ impl App {
                 }
             }
             Message::TabSelected(tab) => {
+                if tab == Tab::History {
+                    self.history
+                        .fetch(&self.pomo.db, self.pomo.config.get_day_end_offset_hours());
+                    self.history_notice = None;
+                }
                 self.current_tab = tab;
             }
             Message::SessionLengthChanged(session_length) => {
@@ -326,6 +576,30 @@ impl App {
                     self.pomo.change_session_length(new_in_min);
                 }
             }
+            Message::ShortBreakLengthChanged(short_break_length) => {
+                self.input_short_break_length = short_break_length;
+                if let Ok(new_in_min) = self.input_short_break_length.parse::<f64>() {
+                    self.pomo
+                        .config
+                        .set_short_break_length(new_in_min, &self.pomo.config_file_path);
+                }
+            }
+            Message::LongBreakLengthChanged(long_break_length) => {
+                self.input_long_break_length = long_break_length;
+                if let Ok(new_in_min) = self.input_long_break_length.parse::<f64>() {
+                    self.pomo
+                        .config
+                        .set_long_break_length(new_in_min, &self.pomo.config_file_path);
+                }
+            }
+            Message::LongBreakIntervalChanged(long_break_interval) => {
+                self.input_long_break_interval = long_break_interval;
+                if let Ok(interval) = self.input_long_break_interval.parse::<u32>() {
+                    self.pomo
+                        .config
+                        .set_long_break_interval(interval, &self.pomo.config_file_path);
+                }
+            }
             Message::ThemeChanged(color_scheme_name) => {
                 self.pomo.change_color_scheme(color_scheme_name);
                 self.update_theme();
@@ -362,47 +636,581 @@ impl App {
                     self.pomo
                         .config
                         .set_day_end_offset_hours(offset_hours, &self.pomo.config_file_path);
+                    self.pomo
+                        .projects
+                        .set_day_end_offset_hours(offset_hours, &self.pomo.db);
+                    self.pomo.tasks.set_day_end_offset_hours(offset_hours);
+                    self.pomo.fetch_streaks();
+                    self.pomo.fetch_completed_task_count();
+                }
+            }
+            Message::DailyGoalChanged(daily_goal) => {
+                self.input_daily_goal = daily_goal;
+                self.store_daily_goal();
+            }
+            Message::DailyGoalUnitSelected(unit) => {
+                self.daily_goal_unit = unit;
+                self.store_daily_goal();
+            }
+            Message::ExportFromChanged(from) => {
+                self.input_export_from = from;
+            }
+            Message::ExportToChanged(to) => {
+                self.input_export_to = to;
+            }
+            Message::ExportSessions(format) => {
+                self.settings_notice = Some(self.export_sessions(format));
+            }
+            Message::ImportPickFile => {
+                let file = rfd::FileDialog::new()
+                    .add_filter("csv", &["csv"])
+                    .pick_file();
+                if let Some(file) = file {
+                    match import::preview(&self.pomo.db, &file, &self.pomo.unrecorded_work()) {
+                        Ok(preview) => {
+                            self.settings_notice = Some(preview.summary());
+                            self.import_preview = Some(preview);
+                        }
+                        Err(e) => self.settings_notice = Some(format!("Import failed: {e}")),
+                    }
+                }
+            }
+            Message::ImportConfirm => {
+                if let Some(preview) = self.import_preview.take() {
+                    self.settings_notice = Some(
+                        match import::apply(&self.pomo.db, &preview, &self.pomo.unrecorded_work()) {
+                            Ok(count) => format!("Imported {count} sessions"),
+                            Err(e) => format!("Import failed: {e}"),
+                        },
+                    );
+                    self.pomo.projects.fetch(&self.pomo.db);
+                    self.pomo.fetch_streaks();
                 }
             }
+            Message::ImportCancel => {
+                self.import_preview = None;
+                self.settings_notice = None;
+            }
+            Message::StatsViewSelected(view) => {
+                self.stats_view = view;
+            }
+            Message::StatsBreakdownParentSelected(parent) => {
+                self.stats_breakdown_parent = parent;
+            }
+            Message::StatsRangeSelected(range) => {
+                self.pomo
+                    .config
+                    .set_stats_range(range, &self.pomo.config_file_path);
+                if range == stats::StatsRange::Custom {
+                    self.store_stats_custom_span();
+                }
+                self.pomo.fetch_completed_task_count();
+            }
+            Message::SaveReport(format) => {
+                self.stats_notice = Some(self.save_report(format));
+            }
+            Message::SaveChart(format) => {
+                self.stats_notice = Some(self.save_chart(format));
+            }
+            Message::StatsFromChanged(from) => {
+                self.input_stats_from = from;
+                self.store_stats_custom_span();
+            }
+            Message::StatsToChanged(to) => {
+                self.input_stats_to = to;
+                self.store_stats_custom_span();
+            }
+            Message::HistoryWeeksBackChanged(weeks_back) => {
+                self.history.set_weeks_back(
+                    weeks_back,
+                    &self.pomo.db,
+                    self.pomo.config.get_day_end_offset_hours(),
+                );
+            }
+            Message::EditSessionInitiate(time_start) => {
+                self.history.initiate_edit(time_start);
+                self.delete_session_button_confirm = false;
+                self.history_notice = None;
+            }
+            Message::EditSessionProjectSelected(id) => {
+                self.history.set_edited_project(id);
+            }
+            Message::EditSessionDurationInput(duration) => {
+                self.history.set_edited_duration(duration);
+            }
+            Message::EditSessionFinish => {
+                let result = self.history.finish_edit(
+                    &self.pomo.db,
+                    &self.pomo.unrecorded_work(),
+                    self.pomo.config.get_day_end_offset_hours(),
+                );
+                self.history_notice = result.err();
+                self.pomo.projects.fetch(&self.pomo.db);
+                self.pomo.fetch_streaks();
+            }
+            Message::EditSessionDelete => {
+                if self.delete_session_button_confirm {
+                    self.history
+                        .delete_edited(&self.pomo.db, self.pomo.config.get_day_end_offset_hours());
+                    self.pomo.projects.fetch(&self.pomo.db);
+                    self.pomo.fetch_streaks();
+                    self.delete_session_button_confirm = false;
+                } else {
+                    self.delete_session_button_confirm = true;
+                }
+            }
+            Message::ManualEntryDateInput(date) => {
+                self.input_manual_date = date;
+            }
+            Message::ManualEntryStartInput(start) => {
+                self.input_manual_start = start;
+            }
+            Message::ManualEntryDurationInput(duration) => {
+                self.input_manual_duration = duration;
+            }
+            Message::ManualEntryProjectSelected(id) => {
+                self.manual_project = Some(id);
+            }
+            Message::ManualEntryAdd => {
+                self.history_notice = Some(self.add_manual_entry());
+            }
             Message::EditTodoTask { id, name } => {
                 let conn = &self.pomo.db;
                 self.pomo.tasks.edit(id, name, conn);
+                self.pomo.tasks.set_selected(Some(id));
+                // a completed task may get or lose its name
+                self.pomo.fetch_completed_task_count();
             }
-            Message::NewTodoTask { name } => {
+            Message::TaskPriorityCycle { id } => {
+                let conn = &self.pomo.db;
+                if let Some(task) = self.pomo.tasks.get_all().iter().find(|t| t.id == id) {
+                    let priority = task.priority.cycle();
+                    self.pomo.tasks.set_priority(id, priority, conn);
+                }
+                self.pomo.tasks.set_selected(Some(id));
+            }
+            Message::TaskDragStart(id) => {
+                self.dragged_task = Some(id);
+                self.drag_target_task = Some(id);
+                self.pomo.tasks.set_selected(Some(id));
+            }
+            Message::TaskDragOver(id) => {
+                self.drag_target_task = Some(id);
+            }
+            Message::TaskDrop(id) => {
+                if let Some(dragged) = self.dragged_task.take() {
+                    let conn = &self.pomo.db;
+                    self.pomo.tasks.move_to(dragged, id, conn);
+                }
+                self.drag_target_task = None;
+            }
+            Message::TaskDragEnd => {
+                self.dragged_task = None;
+                self.drag_target_task = None;
+            }
+            Message::MoveSelectedTask { up } => {
+                let conn = &self.pomo.db;
+                self.pomo.tasks.move_selected(up, conn);
+            }
+            Message::EditTodoTaskEstimate { id, estimate } => {
+                // an empty field clears the estimate, anything else but a number is ignored
+                let estimate = match estimate.trim() {
+                    "" => Some(None),
+                    s => s.parse::<u32>().ok().map(Some),
+                };
+                if let Some(estimate) = estimate {
+                    let conn = &self.pomo.db;
+                    self.pomo.tasks.set_estimate(id, estimate, conn);
+                }
+            }
+            Message::NewTodoTask { name, parent } => {
                 let conn = &self.pomo.db;
                 self.pomo
                     .tasks
-                    .add(name, self.pomo.projects.get_active(), conn);
+                    .add(name, self.pomo.projects.get_active(), parent, conn);
+                self.new_subtask_parent = None;
+            }
+            Message::NewSubTaskInitiate(id) => {
+                self.pomo.tasks.set_collapsed(id, false);
+                self.new_subtask_parent = Some(id);
+            }
+            Message::CompleteTodoTask { id, completed } => {
+                let conn = &self.pomo.db;
+                if !completed {
+                    self.pomo.tasks.reopen(id, conn);
+                    self.pomo.fetch_completed_task_count();
+                } else if self.pomo.tasks.get_open_subtasks(id, conn).is_empty() {
+                    self.complete_todo_task(id, false);
+                } else {
+                    self.complete_task_prompt = Some(id);
+                }
+            }
+            Message::CompleteTodoTaskConfirm { id, subtasks } => {
+                self.complete_task_prompt = None;
+                self.complete_todo_task(id, subtasks);
+            }
+            Message::CompleteTodoTaskCancel => {
+                self.complete_task_prompt = None;
+            }
+            Message::TaskCollapseToggled(id) => {
+                let collapsed = self.pomo.tasks.is_collapsed(id);
+                self.pomo.tasks.set_collapsed(id, !collapsed);
+            }
+            Message::TaskScheduleToggle(id) => {
+                if self.scheduled_task == Some(id) {
+                    self.scheduled_task = None;
+                } else {
+                    self.scheduled_task = Some(id);
+                    self.input_task_due = self
+                        .pomo
+                        .tasks
+                        .get_all()
+                        .iter()
+                        .find(|t| t.id == id)
+                        .and_then(|t| t.due)
+                        .map(|due| due.to_string())
+                        .unwrap_or_default();
+                }
+            }
+            Message::TaskDueInput(due) => {
+                self.input_task_due = due;
+                // an empty field clears the due date, it is set once the date is complete
+                let due = match self.input_task_due.trim() {
+                    "" => Some(None),
+                    s => chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                        .ok()
+                        .map(Some),
+                };
+                if let (Some(id), Some(due)) = (self.scheduled_task, due) {
+                    let conn = &self.pomo.db;
+                    self.pomo.tasks.set_due(id, due, conn);
+                }
+            }
+            Message::TaskRecurrenceSelected(recurrence) => {
+                if let Some(id) = self.scheduled_task {
+                    let conn = &self.pomo.db;
+                    self.pomo.tasks.set_recurrence(id, recurrence, conn);
+                }
+            }
+            Message::CurrentTaskSelected(id) => {
+                self.pomo.select_task(id);
+            }
+            Message::UndoCompleteTodoTask => {
+                let conn = &self.pomo.db;
+                self.pomo.tasks.undo_complete(conn);
+                self.pomo.fetch_completed_task_count();
             }
             Message::DeleteTodoTask { id } => {
+                if self.delete_task_button_confirm == Some(id) {
+                    self.delete_task_button_confirm = None;
+                    let conn = &self.pomo.db;
+                    let deleted = self.pomo.tasks.delete(id, conn);
+                    let is_deleted =
+                        |task: &Option<usize>| task.is_some_and(|t| deleted.contains(&t));
+                    if is_deleted(&self.new_subtask_parent) {
+                        self.new_subtask_parent = None;
+                    }
+                    if is_deleted(&self.complete_task_prompt) {
+                        self.complete_task_prompt = None;
+                    }
+                    if is_deleted(&self.scheduled_task) {
+                        self.scheduled_task = None;
+                    }
+                    self.pomo.fetch_completed_task_count();
+                } else {
+                    self.delete_task_button_confirm = Some(id);
+                }
+            }
+            Message::ShowCompletedTasksToggled(show) => {
                 let conn = &self.pomo.db;
-                self.pomo.tasks.delete(id, conn);
+                self.pomo.tasks.set_show_completed(show, conn);
+            }
+            #[cfg(unix)]
+            Message::Ipc(incoming) => {
+                let result = control::execute(&mut self.pomo, incoming.request);
+                let status = control::status(&self.pomo);
+                incoming
+                    .responder
+                    .respond(control::Response::new(result, Some(status)));
             }
         }
         Task::none()
     }
 
+    // checks off the task, and its open sub-tasks with it if subtasks
+    fn complete_todo_task(&mut self, id: usize, subtasks: bool) {
+        let mut ids = vec![id];
+        if subtasks {
+            ids.append(&mut self.pomo.tasks.get_open_subtasks(id, &self.pomo.db));
+        }
+        // the running session up to now still counts for the task
+        if self
+            .pomo
+            .tasks
+            .get_current()
+            .is_some_and(|current| ids.contains(&current))
+        {
+            self.pomo.select_task(None);
+        }
+        self.pomo.tasks.complete(&ids, &self.pomo.db);
+        self.pomo.fetch_completed_task_count();
+    }
+
+    // returns a message for the settings tab
+    fn export_sessions(&self, format: export::Format) -> String {
+        let parse = |s: &str| chrono::NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d");
+        let (Ok(from), Ok(to)) = (parse(&self.input_export_from), parse(&self.input_export_to))
+        else {
+            return "Dates should look like 2025-01-31".to_owned();
+        };
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(format.extension(), &[format.extension()])
+            .set_file_name(format!("hellowork_{from}_{to}.{}", format.extension()))
+            .save_file()
+        else {
+            return String::new();
+        };
+        let result = std::fs::File::create(&path).and_then(|mut file| {
+            export::export_sessions(
+                &self.pomo.db,
+                from,
+                to,
+                self.pomo.config.get_day_end_offset_hours(),
+                format,
+                &mut file,
+            )
+        });
+        match result {
+            Ok(count) => format!("Exported {count} sessions"),
+            Err(e) => format!("Export failed: {e}"),
+        }
+    }
+
+    // an empty input removes the goal
+    fn store_daily_goal(&mut self) {
+        let daily_goal = if self.input_daily_goal.trim().is_empty() {
+            None
+        } else if let Ok(amount) = self.input_daily_goal.trim().parse::<f32>()
+            && amount > 0.0
+        {
+            Some(config::DailyGoal {
+                amount,
+                unit: self.daily_goal_unit,
+            })
+        } else {
+            return;
+        };
+        self.pomo
+            .config
+            .set_daily_goal(daily_goal, &self.pomo.config_file_path);
+        self.pomo.fetch_streaks();
+    }
+
+    // e.g. ("Today 2.5 / 4 hours", "3 day streak"), None without a daily goal
+    fn daily_goal_strings(&self) -> Option<(String, String)> {
+        let goal = self.pomo.config.get_daily_goal()?;
+        let streaks = self.pomo.get_streaks()?;
+        let today = (streaks.today * 10.0).round() / 10.0;
+        let streak = if streaks.longest > streaks.current {
+            format!(
+                "{} day streak, longest {} days",
+                streaks.current, streaks.longest
+            )
+        } else {
+            format!("{} day streak", streaks.current)
+        };
+        Some((
+            format!("Today {today} / {} {}", goal.amount, goal.unit),
+            streak,
+        ))
+    }
+
+    // keeps the last valid span while the dates are being typed
+    fn store_stats_custom_span(&mut self) {
+        let parse = |s: &str| chrono::NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d");
+        if let (Ok(from), Ok(to)) = (parse(&self.input_stats_from), parse(&self.input_stats_to))
+            && from <= to
+        {
+            self.pomo
+                .config
+                .set_stats_custom_span(from, to, &self.pomo.config_file_path);
+            self.pomo.fetch_completed_task_count();
+        }
+    }
+
+    // returns a message for the stats tab
+    fn save_report(&self, format: report::Format) -> String {
+        let (from, to) = self.pomo.stats_days();
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(format.extension(), &[format.extension()])
+            .set_file_name(format!(
+                "hellowork_report_{from}_{to}.{}",
+                format.extension()
+            ))
+            .save_file()
+        else {
+            return String::new();
+        };
+        let chart_file = (format == report::Format::Markdown).then(|| path.with_extension("svg"));
+        let result = std::fs::File::create(&path).and_then(|mut file| {
+            report::write_report(
+                &self.pomo.db,
+                &self.pomo.config,
+                from,
+                to,
+                format,
+                chart_file.as_deref(),
+                &mut file,
+            )
+        });
+        match result {
+            Ok(()) => format!("Saved report to {}", path.display()),
+            Err(e) => format!("Report failed: {e}"),
+        }
+    }
+
+    // returns a message for the stats tab
+    fn save_chart(&self, format: charts::ImageFormat) -> String {
+        let spec = self.chart_spec();
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(format.extension(), &[format.extension()])
+            .set_file_name(format!(
+                "hellowork_{}.{}",
+                spec.view.to_string().to_lowercase().replace(' ', "_"),
+                format.extension()
+            ))
+            .save_file()
+        else {
+            return String::new();
+        };
+        match charts::save(
+            &path,
+            format,
+            CHART_IMAGE_SIZE,
+            &self.pomo.db,
+            &self.pomo.config,
+            &spec,
+        ) {
+            Ok(()) => format!("Saved chart to {}", path.display()),
+            Err(e) => format!("Saving the chart failed: {e}"),
+        }
+    }
+
+    fn chart_spec(&self) -> charts::ChartSpec {
+        let (first, last) = self.pomo.stats_days();
+        charts::ChartSpec {
+            view: self.stats_view,
+            first,
+            last,
+            breakdown_parent: self.stats_breakdown_parent,
+        }
+    }
+
+    // returns a message for the history tab
+    fn add_manual_entry(&mut self) -> String {
+        let start = chrono::NaiveDate::parse_from_str(self.input_manual_date.trim(), "%Y-%m-%d")
+            .ok()
+            .zip(chrono::NaiveTime::parse_from_str(self.input_manual_start.trim(), "%H:%M").ok())
+            .and_then(|(d, t)| d.and_time(t).and_local_timezone(chrono::Local).earliest());
+        let Some(start) = start else {
+            return "Dates should look like 2025-01-31 and times like 09:30".to_owned();
+        };
+        let result = self.history.add(
+            &self.pomo.db,
+            start,
+            &self.input_manual_duration,
+            self.manual_project,
+            &self.pomo.unrecorded_work(),
+            self.pomo.config.get_day_end_offset_hours(),
+        );
+        match result {
+            Ok(()) => {
+                self.pomo.projects.fetch(&self.pomo.db);
+                self.pomo.fetch_streaks();
+                self.input_manual_start = String::new();
+                self.input_manual_duration = String::new();
+                format!("Added a session on {}", start.format("%b %-d, %H:%M"))
+            }
+            Err(e) => e,
+        }
+    }
+
+    // hours done against the project's target_hours, sub-projects included
+    fn target_progress_bar(
+        &self,
+        project: &projects::Project,
+        width: f32,
+        height: f32,
+    ) -> Element<'_, Message> {
+        let progress = project.target_progress().unwrap_or_default();
+        container(
+            progress_bar(0.0..=1.0, progress.min(1.0)).style(if project.target_reached() {
+                progress_bar::success
+            } else {
+                progress_bar::primary
+            }),
+        )
+        .width(width)
+        .height(height)
+        .into()
+    }
+
+    // projects indented under their parents, for pick lists
+    fn project_options(&self) -> Vec<projects::Project> {
+        self.pomo
+            .projects
+            .get_all_tree_style()
+            .into_iter()
+            .map(|(depth, p)| {
+                let mut p = p.clone();
+                p.name = util::truncate_with_ellipsis(
+                    (0..depth)
+                        .map(|_| "  ")
+                        .chain(iter::once("› "))
+                        .collect::<String>()
+                        + &p.name,
+                    40,
+                );
+                p
+            })
+            .collect()
+    }
+
     fn subscription(&self) -> Subscription<Message> {
+        // while idle it only has to notice the day rolling over
         let tick = if self.pomo.is_running() {
             time::every(Duration::from_secs(1)).map(|_| Message::Tick)
         } else {
-            Subscription::none()
+            time::every(Duration::from_secs(60)).map(|_| Message::Tick)
         };
 
-        fn handle_hotkey(key: keyboard::Key, _modifiers: keyboard::Modifiers) -> Option<Message> {
+        fn handle_hotkey(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
             use keyboard::key;
 
             match key.as_ref() {
                 keyboard::Key::Named(key::Named::Space) => Some(Message::Toggle),
+                keyboard::Key::Character("p") => Some(Message::PauseToggle),
+                // moves the to-do task last clicked or edited
+                keyboard::Key::Named(key::Named::ArrowUp) if modifiers.alt() => {
+                    Some(Message::MoveSelectedTask { up: true })
+                }
+                keyboard::Key::Named(key::Named::ArrowDown) if modifiers.alt() => {
+                    Some(Message::MoveSelectedTask { up: false })
+                }
                 _ => None,
             }
         }
 
-        Subscription::batch(vec![
+        let mut subscriptions = vec![
             tick,
             keyboard::on_key_press(handle_hotkey),
             window::events().map(|ev| Message::StoreWindowGeometry { event: ev.1 }),
-        ])
+        ];
+        // lets the command line and other tools control this instance
+        #[cfg(unix)]
+        subscriptions.push(Subscription::run(ipc::listen).map(Message::Ipc));
+
+        Subscription::batch(subscriptions)
     }
 
     fn title(&self) -> String {
@@ -414,18 +1222,232 @@ impl App {
     }
 
     fn mini_window_view(&self) -> Element<Message> {
+        let phase = text(self.pomo.phase_string()).size(12);
         let duration = text(self.pomo.countdown_string()).size(40);
-        column![center(duration)].into()
+        let target: Element<Message> = match self.pomo.projects.get_active_project() {
+            Some(p) if p.target_hours.is_some() => self.target_progress_bar(p, 100.0, 4.0),
+            _ => Space::new(0, 0).into(),
+        };
+        column![center(column![phase, duration, target].align_x(Center))].into()
+    }
+
+    // a to-do task indented by depth, with its checkbox and inputs
+    fn todo_task_row<'a>(
+        &'a self,
+        depth: usize,
+        task: &'a todo_tasks::TodoTask,
+    ) -> Element<'a, Message> {
+        let is_due = task.is_due(self.pomo.tasks.today());
+        let expand: Element<Message> = if task.children.is_empty() {
+            Space::with_width(TASK_EXPAND_WIDTH).into()
+        } else {
+            button(text(if self.pomo.tasks.is_collapsed(task.id) {
+                "▸"
+            } else {
+                "▾"
+            }))
+            .width(TASK_EXPAND_WIDTH)
+            .padding(0)
+            .style(button::text)
+            .on_press(Message::TaskCollapseToggled(task.id))
+            .into()
+        };
+        let task_row = row![
+            Space::with_width(depth as f32 * TASK_INDENT_WIDTH),
+            expand,
+            // the handle of the task keyboard moves apply to stands out
+            MouseArea::new(text("⠿").width(TASK_HANDLE_WIDTH).style(
+                if self.pomo.tasks.get_selected() == Some(task.id) {
+                    text::primary
+                } else {
+                    text::default
+                }
+            ))
+            .interaction(iced::mouse::Interaction::Grab)
+            .on_press(Message::TaskDragStart(task.id)),
+            checkbox("", task.completed_at.is_some()).on_toggle(|completed| {
+                Message::CompleteTodoTask {
+                    id: task.id,
+                    completed,
+                }
+            }),
+            tooltip(
+                button(text(task.priority.symbol()).center())
+                    .width(TASK_PRIORITY_WIDTH)
+                    .padding([2, 0])
+                    .style(if task.priority == todo_tasks::Priority::High {
+                        button::primary
+                    } else {
+                        button::text
+                    })
+                    .on_press(Message::TaskPriorityCycle { id: task.id }),
+                container(text!("{} priority", task.priority))
+                    .padding(10)
+                    .style(container::rounded_box),
+                tooltip::Position::Top,
+            ),
+            text_input("", &task.name)
+                .style(move |theme: &Theme, status| {
+                    let style = todo_text_input_style(theme, status);
+                    if is_due {
+                        text_input::Style {
+                            value: theme.palette().warning,
+                            ..style
+                        }
+                    } else {
+                        style
+                    }
+                })
+                .on_input(|s| Message::EditTodoTask {
+                    id: task.id,
+                    name: s,
+                }),
+            tooltip(
+                button(
+                    text(match (task.due, task.recurrence) {
+                        (Some(due), Some(_)) => format!("{} ↻", due.format("%b %-d")),
+                        (Some(due), None) => due.format("%b %-d").to_string(),
+                        (None, Some(_)) => "↻".to_owned(),
+                        (None, None) => "Due".to_owned(),
+                    })
+                    .size(14)
+                    .style(move |theme: &Theme| {
+                        if is_due {
+                            text_style_error(theme)
+                        } else {
+                            text::Style::default()
+                        }
+                    })
+                )
+                .padding([2, 4])
+                .style(button::text)
+                .on_press(Message::TaskScheduleToggle(task.id)),
+                container(text(
+                    task.schedule().unwrap_or("Due date and repeat".to_owned())
+                ))
+                .padding(10)
+                .style(container::rounded_box),
+                tooltip::Position::Top,
+            ),
+        ]
+        .push(
+            text_input(
+                "Est.",
+                &task.estimate.map(|e| e.to_string()).unwrap_or_default(),
+            )
+            .width(45)
+            .style(todo_text_input_style)
+            .on_input(|s| Message::EditTodoTaskEstimate {
+                id: task.id,
+                estimate: s,
+            }),
+        )
+        .push_maybe(task.summary().map(|summary| {
+            let over_estimate = task.estimate.is_some_and(|e| task.pomodoros > e);
+            tooltip(
+                text(summary).size(14).style(move |theme: &Theme| {
+                    if over_estimate {
+                        text::danger(theme)
+                    } else {
+                        text::Style::default()
+                    }
+                }),
+                container("Time worked · pomodoros completed / estimated")
+                    .padding(10)
+                    .style(container::rounded_box),
+                tooltip::Position::Top,
+            )
+        }))
+        .push(tooltip(
+            button(
+                svg(svg::Handle::from_memory(ADD_ICON))
+                    .style(svg_style_primary)
+                    .height(12)
+                    .width(12),
+            )
+            .style(button_style_transparent)
+            .on_press(Message::NewSubTaskInitiate(task.id)),
+            container("Add sub-task")
+                .padding(10)
+                .style(container::rounded_box),
+            tooltip::Position::Top,
+        ))
+        .push(tooltip(
+            button(
+                svg(svg::Handle::from_memory(ARCHIVE_ICON))
+                    .style(svg_style_primary)
+                    .height(12)
+                    .width(12),
+            )
+            .style(if self.delete_task_button_confirm == Some(task.id) {
+                button::danger
+            } else {
+                button_style_transparent
+            })
+            .on_press(Message::DeleteTodoTask { id: task.id }),
+            container(if self.delete_task_button_confirm == Some(task.id) {
+                "Click again to delete, with its sub-tasks"
+            } else {
+                "Delete task"
+            })
+            .padding(10)
+            .style(container::rounded_box),
+            tooltip::Position::Top,
+        ))
+        .spacing(10)
+        .align_y(Center);
+        // dropping a task on another moves it there
+        let is_drop_target = self.dragged_task.is_some() && self.drag_target_task == Some(task.id);
+        MouseArea::new(container(task_row).style(if is_drop_target {
+            container::rounded_box
+        } else {
+            container::transparent
+        }))
+        .on_enter(Message::TaskDragOver(task.id))
+        .on_release(Message::TaskDrop(task.id))
+        .into()
+    }
+
+    // the due date and recurrence of a to-do task
+    fn todo_task_schedule_editor(&self, task: &todo_tasks::TodoTask) -> Element<'_, Message> {
+        container(
+            row![
+                text("Due"),
+                text_input("YYYY-MM-DD", &self.input_task_due)
+                    .width(110)
+                    .on_input(Message::TaskDueInput),
+                pick_list(
+                    todo_tasks::Recurrence::options(task.due.unwrap_or(self.pomo.tasks.today())),
+                    task.recurrence,
+                    |r| { Message::TaskRecurrenceSelected(Some(r)) }
+                )
+                .placeholder("Doesn't repeat"),
+            ]
+            .push_maybe(task.recurrence.map(|_| {
+                button("Don't repeat")
+                    .style(button::secondary)
+                    .on_press(Message::TaskRecurrenceSelected(None))
+            }))
+            .push(button("Done").on_press(Message::TaskScheduleToggle(task.id)))
+            .spacing(7)
+            .align_y(Center),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
     }
 
     fn main_tab_view(&self) -> Element<Message> {
+        let phase = text(self.pomo.phase_string());
         let duration = text(self.pomo.countdown_string()).size(40);
 
         let toggle_button = {
-            let label = if self.pomo.is_running() {
-                "Stop"
-            } else {
+            let label = if !self.pomo.is_running() {
                 "Start"
+            } else if self.pomo.is_break() {
+                "Skip"
+            } else {
+                "Stop"
             };
 
             button(text(label).align_x(Center))
@@ -434,75 +1456,238 @@ impl App {
                 .on_press(Message::Toggle)
         };
 
+        let pause_button = if self.pomo.is_running() {
+            let label = if self.pomo.is_paused() {
+                "Resume"
+            } else {
+                "Pause"
+            };
+
+            row![
+                button(text(label).align_x(Center))
+                    .padding(10)
+                    .width(80)
+                    .style(button::secondary)
+                    .on_press(Message::PauseToggle)
+            ]
+        } else {
+            row![]
+        };
+
         let project_picker = pick_list(
-            self.pomo
-                .projects
-                .get_all_tree_style()
-                .into_iter()
-                .map(|(depth, p)| {
-                    let mut p = p.clone();
-                    p.name = util::truncate_with_ellipsis(
-                        (0..depth)
-                            .map(|_| "  ")
-                            .chain(iter::once("› "))
-                            .collect::<String>()
-                            + &p.name,
-                        40,
-                    );
-                    p
-                })
-                .collect::<Vec<_>>(),
+            self.project_options(),
             self.pomo.projects.get_active_project(),
             |p| Message::ProjectSelected(p.id),
         );
 
-        // even though we did nothing to switch focus to the new text_input
-        // it happens anyway by pure chance, nice
-        let todo_list: Element<Message> = scrollable(
-            column(self.pomo.tasks.get_all().iter().map(|task| {
+        let target: Element<Message> = match self.pomo.projects.get_active_project() {
+            Some(p) if p.target_hours.is_some() => {
+                let target_hours = p.target_hours.unwrap_or_default();
+                column![
+                    self.target_progress_bar(p, 200.0, 8.0),
+                    if p.target_reached() {
+                        text!("Goal of {target_hours} hours reached").style(text::success)
+                    } else {
+                        text!(
+                            "{} / {target_hours} hours",
+                            (p.total_hours * 10.0).round() / 10.0
+                        )
+                    }
+                    .size(14)
+                ]
+                .align_x(Center)
+                .spacing(5)
+                .into()
+            }
+            _ => Space::new(0, 0).into(),
+        };
+
+        let daily_goal: Element<Message> = match self.daily_goal_strings() {
+            Some((today, streak)) => text!("{today}  ·  {streak}").size(14).into(),
+            None => Space::new(0, 0).into(),
+        };
+
+        let open_tasks: Vec<&todo_tasks::TodoTask> = self
+            .pomo
+            .tasks
+            .get_all()
+            .iter()
+            .filter(|t| t.completed_at.is_none())
+            .collect();
+        let task_picker: Element<Message> =
+            if self.pomo.config.get_todo_tasks_enabled() && !open_tasks.is_empty() {
                 row![
-                    checkbox("", false).on_toggle(|_state| Message::DeleteTodoTask { id: task.id }),
-                    text_input("", &task.name)
-                        .style(todo_text_input_style)
-                        .on_input(|s| Message::EditTodoTask {
-                            id: task.id,
-                            name: s,
-                        })
+                    pick_list(
+                        open_tasks,
+                        self.pomo.tasks.get_current_task(),
+                        |t: &todo_tasks::TodoTask| Message::CurrentTaskSelected(Some(t.id)),
+                    )
+                    .placeholder("No task")
                 ]
+                .push_maybe(self.pomo.tasks.get_current().map(|_| {
+                    button("Clear")
+                        .style(button::secondary)
+                        .on_press(Message::CurrentTaskSelected(None))
+                }))
+                .spacing(7)
                 .align_y(Center)
                 .into()
-            }))
-            .extend(iter::once(
+            } else {
+                Space::new(0, 0).into()
+            };
+
+        let budget_warning: Element<Message> = match self.pomo.budget_warning() {
+            Some(warning) if self.pomo.is_running() && !self.pomo.is_break() => {
+                text(warning).size(14).style(text::danger).into()
+            }
+            _ => Space::new(0, 0).into(),
+        };
+
+        let recovery_prompt: Element<Message> = if let Some(r) = &self.pomo.recovered {
+            let started = chrono::DateTime::from_timestamp(r.session_start as i64, 0)
+                .map(|t| {
+                    t.with_timezone(&chrono::Local)
+                        .format("%b %-d, %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            container(
+                column![
+                    text!("Unfinished session from {started}"),
+                    row![
+                        button("Resume").on_press(Message::RecoveredSessionResume),
+                        button("Save Partial")
+                            .style(button::secondary)
+                            .on_press(Message::RecoveredSessionSavePartial),
+                        button("Discard")
+                            .style(button::danger)
+                            .on_press(Message::RecoveredSessionDiscard),
+                    ]
+                    .spacing(7)
+                ]
+                .align_x(Center)
+                .spacing(10),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+            .into()
+        } else {
+            Space::new(0, 0).into()
+        };
+
+        // even though we did nothing to switch focus to the new text_input
+        // it happens anyway by pure chance, nice
+        let new_task_row = |depth: usize, parent: Option<usize>| -> Element<Message> {
+            row![
+                Space::with_width(depth as f32 * TASK_INDENT_WIDTH),
+                Space::with_width(TASK_EXPAND_WIDTH),
+                Space::with_width(TASK_HANDLE_WIDTH),
+                checkbox("", false),
+                Space::with_width(TASK_PRIORITY_WIDTH),
+                text_input("...", "")
+                    .style(todo_text_input_style)
+                    .on_input(move |s| Message::NewTodoTask { name: s, parent })
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        };
+        let complete_prompt = |id: usize| -> Element<Message> {
+            container(
                 row![
-                    checkbox("", false),
-                    text_input("...", "")
-                        .style(todo_text_input_style)
-                        .on_input(|s| Message::NewTodoTask { name: s })
+                    text("Complete its sub-tasks too?"),
+                    button("Complete All")
+                        .on_press(Message::CompleteTodoTaskConfirm { id, subtasks: true }),
+                    button("Only This").style(button::secondary).on_press(
+                        Message::CompleteTodoTaskConfirm {
+                            id,
+                            subtasks: false
+                        }
+                    ),
+                    button("Cancel")
+                        .style(button::secondary)
+                        .on_press(Message::CompleteTodoTaskCancel),
                 ]
-                .align_y(Center)
-                .into(),
-            ))
-            .padding([0, 20]),
+                .spacing(7)
+                .align_y(Center),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+            .into()
+        };
+        let mut todo_rows: Vec<Element<Message>> = vec![];
+        // the depth of the task a sub-task is being added to, while going through its sub-tasks
+        let mut subtask_parent_depth = None;
+        for (depth, task) in self.pomo.tasks.get_all_tree_style() {
+            // the new sub-task goes below the last one of its parent
+            if let Some(parent_depth) = subtask_parent_depth
+                && depth <= parent_depth
+            {
+                todo_rows.push(new_task_row(parent_depth + 1, self.new_subtask_parent));
+                subtask_parent_depth = None;
+            }
+            todo_rows.push(self.todo_task_row(depth, task));
+            if self.complete_task_prompt == Some(task.id) {
+                todo_rows.push(complete_prompt(task.id));
+            }
+            if self.scheduled_task == Some(task.id) {
+                todo_rows.push(self.todo_task_schedule_editor(task));
+            }
+            if self.new_subtask_parent == Some(task.id) {
+                subtask_parent_depth = Some(depth);
+            }
+        }
+        if let Some(parent_depth) = subtask_parent_depth {
+            todo_rows.push(new_task_row(parent_depth + 1, self.new_subtask_parent));
+        }
+        todo_rows.push(new_task_row(0, None));
+        let todo_list: Element<Message> = scrollable(
+            column(todo_rows)
+                .push(
+                    row![
+                        checkbox("Show completed", self.pomo.tasks.get_show_completed())
+                            .on_toggle(Message::ShowCompletedTasksToggled)
+                    ]
+                    .push_maybe((!self.pomo.tasks.get_last_completed().is_empty()).then(|| {
+                        button("Undo")
+                            .style(button::secondary)
+                            .on_press(Message::UndoCompleteTodoTask)
+                    }))
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .padding([0, 20]),
         )
         .height(Length::FillPortion(3))
         .into();
 
-        column![
-            Space::new(0, Length::FillPortion(2)),
-            center(
-                column![duration, toggle_button, project_picker]
+        // a drag let go anywhere but on a task is dropped
+        MouseArea::new(
+            column![
+                Space::new(0, Length::FillPortion(2)),
+                center(
+                    column![
+                        recovery_prompt,
+                        column![phase, duration, daily_goal].align_x(Center),
+                        row![toggle_button, pause_button].spacing(10),
+                        column![project_picker, task_picker, target, budget_warning]
+                            .align_x(Center)
+                            .spacing(10)
+                    ]
                     .align_x(Center)
                     .spacing(20)
-            )
-            .height(Length::FillPortion(5)),
-            if self.pomo.config.get_todo_tasks_enabled() {
-                todo_list
-            } else {
-                Space::new(0, 0).height(Length::FillPortion(2)).into()
-            }
-        ]
-        .height(Length::Fill)
-        .padding(30)
+                )
+                .height(Length::FillPortion(5)),
+                if self.pomo.config.get_todo_tasks_enabled() {
+                    todo_list
+                } else {
+                    Space::new(0, 0).height(Length::FillPortion(2)).into()
+                }
+            ]
+            .height(Length::Fill)
+            .padding(30),
+        )
+        .on_release(Message::TaskDragEnd)
         .into()
     }
 
@@ -533,6 +1718,37 @@ impl App {
                         )
                         .width(Length::Fill)
                         .on_input(Message::EditProjectNameInput),
+                        tooltip(
+                            text_input("Target", &self.input_edited_target_hours)
+                                .width(70)
+                                .on_input(Message::EditProjectTargetInput),
+                            container(
+                                "Target hours, sub-projects count towards it. Leave empty for none"
+                            )
+                            .padding(10)
+                            .style(container::rounded_box),
+                            tooltip::Position::Bottom,
+                        ),
+                        tooltip(
+                            text_input("Budget", &self.input_edited_budget_hours)
+                                .width(70)
+                                .on_input(Message::EditProjectBudgetInput),
+                            container(
+                                "Hours to spend per day, week or month, sub-projects included. Leave empty for none"
+                            )
+                            .padding(10)
+                            .style(container::rounded_box),
+                            tooltip::Position::Bottom,
+                        ),
+                        pick_list(
+                            projects::BudgetPeriod::ALL,
+                            self.pomo
+                                .projects
+                                .get_edited()
+                                .and_then(|p| p.budget_period),
+                            Message::EditProjectBudgetPeriodSelected,
+                        )
+                        .placeholder("Period"),
                         row![
                             button(
                                 svg(svg::Handle::from_memory(OKAY_ICON))
@@ -570,7 +1786,28 @@ impl App {
                         .width(Length::Fill),
                         if self.pomo.projects.get_edited_id().is_none() {
                             row![
-                                text!("{:<4}", (p.total_hours * 10.0).round() / 10.0),
+                                if p.target_hours.is_some() {
+                                    row![
+                                        self.target_progress_bar(p, 60.0, 6.0),
+                                        text!("{:<4}", (p.total_hours * 10.0).round() / 10.0),
+                                        text!("/ {:<4}", p.target_hours.unwrap_or_default())
+                                            .size(12),
+                                    ]
+                                    .spacing(5)
+                                    .align_y(Center)
+                                } else {
+                                    row![text!("{:<4}", (p.total_hours * 10.0).round() / 10.0)]
+                                },
+                                match p.budget_string() {
+                                    Some(budget) => text(budget).size(12).style(
+                                        if p.budget_remaining().unwrap_or_default() < 0.0 {
+                                            text::danger
+                                        } else {
+                                            text::default
+                                        },
+                                    ),
+                                    None => text(""),
+                                },
                                 button(
                                     svg(svg::Handle::from_memory(CONFIG_ICON))
                                         .style(svg_style)
@@ -604,8 +1841,299 @@ impl App {
         scrollable(column![center_x(projects_list), center_x(new_button)].padding(20)).into()
     }
 
+    fn history_tab_view(&self) -> Element<'_, Message> {
+        let offset_hours = self.pomo.config.get_day_end_offset_hours();
+        let weeks_back = self.history.get_weeks_back();
+        let (first_day, last_day) = self.history.get_days();
+
+        let navigation = row![
+            button("‹").on_press(Message::HistoryWeeksBackChanged(weeks_back + 1)),
+            text!(
+                "{} - {}",
+                first_day.format("%b %-d"),
+                last_day.format("%b %-d")
+            )
+            .width(150)
+            .align_x(Center),
+            button("›").on_press_maybe(
+                weeks_back
+                    .checked_sub(1)
+                    .map(Message::HistoryWeeksBackChanged)
+            ),
+        ]
+        .spacing(7)
+        .align_y(Center);
+
+        let session_row = |session: &db::ExportedWorkSession| -> Element<Message> {
+            let start = util::local_time(session.time_start);
+            let end = util::local_time(session.time_start + session.duration);
+            let times = text!("{} - {}", start.format("%H:%M"), end.format("%H:%M")).width(110);
+            let project_name = session
+                .project_path
+                .clone()
+                .map(|path| {
+                    if session.archived {
+                        path + " (archived)"
+                    } else {
+                        path
+                    }
+                })
+                .unwrap_or("No project".to_owned());
+
+            match self.history.get_edited() {
+                Some(edited) if edited.time_start == session.time_start => row![
+                    times,
+                    pick_list(
+                        self.project_options(),
+                        edited.project_id.and_then(|id| self.pomo.projects.get(id)),
+                        |p| Message::EditSessionProjectSelected(p.id),
+                    )
+                    .placeholder(project_name)
+                    .width(Length::Fill),
+                    tooltip(
+                        text_input("", &edited.duration)
+                            .width(60)
+                            .on_input(Message::EditSessionDurationInput)
+                            .on_submit(Message::EditSessionFinish),
+                        container("Minutes")
+                            .padding(10)
+                            .style(container::rounded_box),
+                        tooltip::Position::Bottom,
+                    ),
+                    button(
+                        svg(svg::Handle::from_memory(OKAY_ICON))
+                            .style(svg_style)
+                            .height(16)
+                            .width(16)
+                    )
+                    .on_press(Message::EditSessionFinish),
+                    button(
+                        svg(svg::Handle::from_memory(ARCHIVE_ICON))
+                            .style(svg_style)
+                            .height(16)
+                            .width(16)
+                    )
+                    .style(if self.delete_session_button_confirm {
+                        button::danger
+                    } else {
+                        button::secondary
+                    })
+                    .on_press(Message::EditSessionDelete),
+                ]
+                .spacing(5)
+                .align_y(Center)
+                .height(32)
+                .into(),
+                _ => row![
+                    times,
+                    text(util::truncate_with_ellipsis(project_name, 40)).width(Length::Fill),
+                    text(util::format_duration(session.duration)),
+                    if self.history.get_edited().is_none() {
+                        row![
+                            button(
+                                svg(svg::Handle::from_memory(CONFIG_ICON))
+                                    .style(svg_style)
+                                    .height(16)
+                                    .width(16)
+                            )
+                            .on_press(Message::EditSessionInitiate(session.time_start))
+                        ]
+                    } else {
+                        row![]
+                    }
+                ]
+                .spacing(5)
+                .align_y(Center)
+                .height(32)
+                .into(),
+            }
+        };
+
+        let days = self.history.get_by_day(offset_hours);
+        let sessions_list: Element<Message> = if days.is_empty() {
+            text("No sessions recorded").into()
+        } else {
+            column(days.into_iter().map(|(day, sessions)| {
+                let total: u64 = sessions.iter().map(|s| s.duration).sum();
+                column![
+                    row![
+                        text(day.format("%A, %b %-d").to_string()).width(Length::Fill),
+                        text(util::format_duration(total)),
+                    ]
+                    .padding(Padding::ZERO.right(38)),
+                    column(sessions.into_iter().map(session_row)).spacing(5),
+                ]
+                .spacing(5)
+                .into()
+            }))
+            .spacing(20)
+            .into()
+        };
+
+        let manual_entry = column![
+            text("Add a session done away from the timer"),
+            row![
+                text_input("2025-01-31", &self.input_manual_date)
+                    .width(110)
+                    .on_input(Message::ManualEntryDateInput),
+                text_input("09:30", &self.input_manual_start)
+                    .width(70)
+                    .on_input(Message::ManualEntryStartInput),
+                text_input("Minutes", &self.input_manual_duration)
+                    .width(80)
+                    .on_input(Message::ManualEntryDurationInput)
+                    .on_submit(Message::ManualEntryAdd),
+                pick_list(
+                    self.project_options(),
+                    self.manual_project
+                        .and_then(|id| self.pomo.projects.get(id)),
+                    |p| Message::ManualEntryProjectSelected(p.id),
+                )
+                .placeholder("No project")
+                .width(Length::Fill),
+                button(
+                    svg(svg::Handle::from_memory(ADD_ICON))
+                        .style(svg_style)
+                        .height(16)
+                        .width(16)
+                )
+                .on_press(Message::ManualEntryAdd),
+            ]
+            .spacing(5)
+            .align_y(Center),
+            text(self.history_notice.as_deref().unwrap_or_default()),
+        ]
+        .spacing(10);
+
+        scrollable(
+            center_x(
+                column![center_x(navigation), sessions_list, manual_entry]
+                    .spacing(20)
+                    .max_width(550),
+            )
+            .padding(20),
+        )
+        .into()
+    }
+
     fn stats_tab_view(&self) -> Element<Message> {
-        Chart::from_program(self).into()
+        let view_picker = pick_list(
+            stats::StatsView::ALL,
+            Some(self.stats_view),
+            Message::StatsViewSelected,
+        );
+
+        let breakdown_picker = if self.stats_view == stats::StatsView::ByProject {
+            row![
+                pick_list(
+                    self.project_options()
+                        .into_iter()
+                        .filter(|p| !p.children.is_empty())
+                        .collect::<Vec<_>>(),
+                    self.stats_breakdown_parent
+                        .and_then(|id| self.pomo.projects.get(id)),
+                    |p| Message::StatsBreakdownParentSelected(Some(p.id)),
+                )
+                .placeholder("Top-level projects"),
+                button("All").style(button::secondary).on_press_maybe(
+                    self.stats_breakdown_parent
+                        .map(|_| Message::StatsBreakdownParentSelected(None))
+                ),
+            ]
+            .spacing(7)
+        } else {
+            row![]
+        };
+
+        let daily_goal = match self.daily_goal_strings() {
+            Some((today, streak)) => column![text(today), text(streak)].align_x(Center),
+            None => column![],
+        };
+
+        // the heatmap always covers the last year
+        let has_range = self.stats_view != stats::StatsView::Heatmap;
+
+        let range_picker = if has_range {
+            row![pick_list(
+                stats::StatsRange::ALL,
+                Some(self.pomo.config.get_stats_range()),
+                Message::StatsRangeSelected,
+            )]
+        } else {
+            row![]
+        };
+
+        // reports cover the picked range, so they go with the range picker
+        let report_buttons = if has_range {
+            row![
+                text("Report: "),
+                button("Markdown")
+                    .style(button::secondary)
+                    .on_press(Message::SaveReport(report::Format::Markdown)),
+                button("HTML")
+                    .style(button::secondary)
+                    .on_press(Message::SaveReport(report::Format::Html)),
+            ]
+            .spacing(7)
+            .align_y(Center)
+        } else {
+            row![]
+        };
+        let save_buttons = row![
+            text("Save Chart: "),
+            button("PNG")
+                .style(button::secondary)
+                .on_press(Message::SaveChart(charts::ImageFormat::Png)),
+            button("SVG")
+                .style(button::secondary)
+                .on_press(Message::SaveChart(charts::ImageFormat::Svg)),
+            report_buttons,
+        ]
+        .spacing(7)
+        .align_y(Center);
+
+        let custom_span =
+            if has_range && self.pomo.config.get_stats_range() == stats::StatsRange::Custom {
+                row![
+                    text_input("From", &self.input_stats_from)
+                        .width(110)
+                        .on_input(Message::StatsFromChanged),
+                    text(" - "),
+                    text_input("To", &self.input_stats_to)
+                        .width(110)
+                        .on_input(Message::StatsToChanged),
+                ]
+                .align_y(Center)
+            } else {
+                row![]
+            };
+
+        let completed_tasks = match self.pomo.get_completed_task_count() {
+            Some(count) if has_range => row![text(match count {
+                1 => "1 task completed".to_owned(),
+                n => format!("{n} tasks completed"),
+            })],
+            _ => row![],
+        };
+
+        column![
+            center_x(
+                column![
+                    row![view_picker, range_picker].spacing(7),
+                    custom_span,
+                    breakdown_picker,
+                    save_buttons,
+                    daily_goal,
+                    completed_tasks,
+                    text(self.stats_notice.as_deref().unwrap_or_default())
+                ]
+                .align_x(Center)
+                .spacing(7)
+            ),
+            Chart::from_program(self)
+        ]
+        .padding(Padding::ZERO.top(10))
+        .into()
     }
 
     fn settings_tab_view(&self) -> Element<Message> {
@@ -630,6 +2158,46 @@ impl App {
                         .on_input(Message::SessionLengthChanged)
                 ]
                 .align_y(Center),
+                row![
+                    text("Short Break: "),
+                    text_input("", &self.input_short_break_length)
+                        .width(70)
+                        .on_input(Message::ShortBreakLengthChanged)
+                ]
+                .align_y(Center),
+                row![
+                    text("Long Break: "),
+                    text_input("", &self.input_long_break_length)
+                        .width(70)
+                        .on_input(Message::LongBreakLengthChanged)
+                ]
+                .align_y(Center),
+                row![
+                    text("Long Break Every: "),
+                    tooltip(
+                        text_input("", &self.input_long_break_interval)
+                            .width(70)
+                            .on_input(Message::LongBreakIntervalChanged),
+                        container("Number of work sessions before a long break")
+                            .padding(10)
+                            .style(container::rounded_box),
+                        tooltip::Position::Bottom,
+                    )
+                ]
+                .align_y(Center),
+                row![
+                    text("Daily Goal: "),
+                    text_input("", &self.input_daily_goal)
+                        .width(70)
+                        .on_input(Message::DailyGoalChanged),
+                    pick_list(
+                        config::GoalUnit::ALL,
+                        Some(self.daily_goal_unit),
+                        Message::DailyGoalUnitSelected,
+                    ),
+                ]
+                .spacing(5)
+                .align_y(Center),
                 row![
                     text("Day Ends @: "),
                     tooltip(
@@ -702,6 +2270,46 @@ impl App {
                     ),
                 ]
                 .align_y(Center),
+                row![
+                    text("Export: "),
+                    text_input("From", &self.input_export_from)
+                        .width(110)
+                        .on_input(Message::ExportFromChanged),
+                    text(" - "),
+                    text_input("To", &self.input_export_to)
+                        .width(110)
+                        .on_input(Message::ExportToChanged),
+                ]
+                .align_y(Center),
+                row![
+                    button("CSV").on_press(Message::ExportSessions(export::Format::Csv)),
+                    button("JSON").on_press(Message::ExportSessions(export::Format::Json)),
+                ]
+                .spacing(7)
+                .align_y(Center),
+                row![
+                    text("Import: "),
+                    tooltip(
+                        button("Pick").on_press(Message::ImportPickFile),
+                        container("CSV export from Toggl Track or Clockify. Clients become projects with their projects under them.")
+                            .padding(10)
+                            .style(container::rounded_box),
+                        tooltip::Position::Bottom,
+                    ),
+                ]
+                .align_y(Center),
+                text(self.settings_notice.as_deref().unwrap_or_default()),
+                if self.import_preview.is_some() {
+                    row![
+                        button("Import").on_press(Message::ImportConfirm),
+                        button("Cancel")
+                            .style(button::secondary)
+                            .on_press(Message::ImportCancel),
+                    ]
+                    .spacing(7)
+                } else {
+                    row![]
+                },
             ]
             .spacing(10)
             .max_width(500)
@@ -716,6 +2324,7 @@ impl App {
         let tabs = row![
             button("Main").on_press(Message::TabSelected(Tab::Main)),
             button("Projects").on_press(Message::TabSelected(Tab::Projects)),
+            button("History").on_press(Message::TabSelected(Tab::History)),
             button("Stats").on_press(Message::TabSelected(Tab::Stats)),
             button("Settings").on_press(Message::TabSelected(Tab::Settings))
         ]
@@ -741,6 +2350,7 @@ impl App {
                 match self.current_tab {
                     Tab::Main => self.main_tab_view(),
                     Tab::Projects => self.projects_tab_view(),
+                    Tab::History => self.history_tab_view(),
                     Tab::Stats => self.stats_tab_view(),
                     Tab::Settings => self.settings_tab_view(),
                 }
@@ -752,6 +2362,13 @@ impl App {
             .on_press(Message::DragMove)
             .on_double_click(Message::MiniWindowToggle);
 
+        // the mini window has no room for buttons, right click pauses instead
+        let mouse_area = if self.mini_window {
+            mouse_area.on_right_press(Message::PauseToggle)
+        } else {
+            mouse_area
+        };
+
         mouse_area.into()
     }
 
@@ -790,54 +2407,6 @@ impl pliced::Program<Message> for App {
         _bounds: iced::Rectangle,
         _cursor: iced::mouse::Cursor,
     ) {
-        let day_end_offset_hours = self.pomo.config.get_day_end_offset_hours();
-        let data = stats::last_week_chart(&self.pomo.db, day_end_offset_hours);
-
-        let sub_c = &self.pomo.config.get_color_scheme().sub_color;
-        let color = plotters::style::RGBColor(sub_c.r, sub_c.g, sub_c.b);
-
-        let text_c = &self.pomo.config.get_color_scheme().text_color;
-        let text_color = plotters::style::RGBColor(text_c.r, text_c.g, text_c.b);
-
-        let style = ShapeStyle {
-            color: color.into(),
-            filled: true,
-            stroke_width: 2,
-        };
-
-        let x_min = data.last().unwrap().0;
-        let x_max = data.first().unwrap().0;
-
-        let y_max = data
-            .iter()
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .unwrap()
-            .1;
-
-        let mut chart = chart
-            .margin(5)
-            .x_label_area_size(30)
-            .y_label_area_size(30)
-            .build_cartesian_2d(x_min..x_max, 0.0_f32..y_max)
-            .unwrap();
-
-        chart
-            .configure_mesh()
-            .label_style(TextStyle::from(("sans-serif", 15).into_font()).color(&text_color))
-            // take out the year display from the dates
-            .x_label_formatter(&|x| format!("{}-{}", x.month(), x.day()))
-            .draw()
-            .unwrap();
-
-        chart
-            .draw_series(
-                AreaSeries::new(
-                    data.iter().map(|x| *x), // The data iter
-                    0.0,                     // Baseline
-                    &color.mix(0.2),         // Make the series opac
-                )
-                .border_style(style), // Make a brighter border
-            )
-            .unwrap();
+        charts::draw(chart, &self.pomo.db, &self.pomo.config, &self.chart_spec()).unwrap();
     }
 }
@@ -0,0 +1,209 @@
+use rusqlite::Connection;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+// Each step brings the schema from version N to N + 1, the version is kept in PRAGMA user_version.
+// Never edit a step that has been released, append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema, also adopts databases created before versioning existed
+    include_str!("schema.sql"),
+    // 2: sessions started from the command line
+    "ALTER TABLE active_session ADD COLUMN headless BOOLEAN NOT NULL DEFAULT FALSE;",
+    // 3: recurring budgets per project, the period is 'day', 'week' or 'month'
+    "ALTER TABLE projects ADD COLUMN budget_hours REAL;
+    ALTER TABLE projects ADD COLUMN budget_period TEXT;",
+    // 4: sessions recorded when a pomodoro ran to the end, older ones can't be told apart
+    "ALTER TABLE work ADD COLUMN completed BOOLEAN NOT NULL DEFAULT FALSE;",
+    // 5: checked off tasks are kept with the time they were completed, NULL while open
+    "ALTER TABLE tasks ADD COLUMN completed_at INTEGER;",
+    // 6: the to-do task worked on, if one was picked
+    "ALTER TABLE work ADD COLUMN task_id INTEGER REFERENCES tasks (id);
+    ALTER TABLE active_session ADD COLUMN task_id INTEGER;",
+    // 7: the pomodoros a task is expected to take
+    "ALTER TABLE tasks ADD COLUMN estimate INTEGER;",
+    // 8: manual order of the tasks within their priority, priority is -1 low, 0 normal, 1 high
+    "ALTER TABLE tasks ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE tasks ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+    UPDATE tasks SET position = id;",
+    // 9: sub-tasks, NULL for top-level tasks
+    "ALTER TABLE tasks ADD COLUMN parent INTEGER REFERENCES tasks (id);",
+    // 10: due dates as YYYY-MM-DD, recurrence is 'daily', 'weekdays', 'weekly mon' and so on
+    // or 'monthly 31' for the day of the month
+    "ALTER TABLE tasks ADD COLUMN due TEXT;
+    ALTER TABLE tasks ADD COLUMN recurrence TEXT;",
+    // 11: the completed recurring task a task was added to repeat
+    "ALTER TABLE tasks ADD COLUMN repeat_of INTEGER REFERENCES tasks (id);",
+];
+
+pub const LATEST_VERSION: usize = MIGRATIONS.len();
+
+#[derive(Debug)]
+pub enum MigrationError {
+    TooNew { db_version: usize },
+    Backup(std::io::Error),
+    Sql(rusqlite::Error),
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::TooNew { db_version } => write!(
+                f,
+                "The database was written by a newer version of Hello Work (schema version {db_version}, \
+                this version supports up to {LATEST_VERSION}). Please update Hello Work."
+            ),
+            MigrationError::Backup(e) => {
+                write!(f, "Could not back up the database before upgrading it: {e}")
+            }
+            MigrationError::Sql(e) => write!(f, "Upgrading the database failed: {e}"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sql(e)
+    }
+}
+
+pub fn get_version(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+// path is the database file, used for the backup, None for in-memory databases
+pub fn migrate(conn: &mut Connection, path: Option<&Path>) -> Result<(), MigrationError> {
+    let db_version = get_version(conn)?;
+    if db_version > LATEST_VERSION {
+        return Err(MigrationError::TooNew { db_version });
+    }
+    if db_version == LATEST_VERSION {
+        return Ok(());
+    }
+
+    // a fresh database has nothing worth backing up
+    let table_count: usize =
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", (), |row| row.get(0))?;
+    if let Some(path) = path.filter(|_| table_count > 0) {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".v{db_version}.bak"));
+        fs::copy(path, backup).map_err(MigrationError::Backup)?;
+    }
+
+    for (i, step) in MIGRATIONS.iter().enumerate().skip(db_version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(step)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the schema as it was before versioning, user_version left at 0
+    const UNVERSIONED_SCHEMA: &str = "
+        CREATE TABLE projects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            target_hours REAL,
+            parent INTEGER,
+            archived BOOLEAN NOT NULL DEFAULT FALSE,
+            FOREIGN KEY (parent) REFERENCES projects (id)
+        );
+        CREATE TABLE work (
+            time_start INTEGER NOT NULL PRIMARY KEY,
+            duration INTEGER NOT NULL,
+            project_id INTEGER,
+            FOREIGN KEY (project_id) REFERENCES projects (id)
+        );
+        CREATE TABLE tasks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            project_id INTEGER,
+            FOREIGN KEY (project_id) REFERENCES projects (id)
+        );
+        CREATE VIEW work_totals AS
+            SELECT project_id, SUM(duration) AS duration FROM work GROUP BY project_id;
+        INSERT INTO projects (name) VALUES ('Old project');
+        INSERT INTO work (time_start, duration, project_id) VALUES (1700000000, 1500, 1);
+        INSERT INTO tasks (name, project_id) VALUES ('Old task', 1);";
+
+    fn column_exists(conn: &Connection, table: &str, column: &str) -> bool {
+        conn.prepare(&format!("SELECT {column} FROM {table}"))
+            .is_ok()
+    }
+
+    #[test]
+    fn migrates_empty_database_to_latest() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn, None).unwrap();
+        assert_eq!(get_version(&conn).unwrap(), LATEST_VERSION);
+        assert!(column_exists(&conn, "active_session", "task_id"));
+        assert!(column_exists(&conn, "tasks", "recurrence"));
+        // running again on a current database changes nothing
+        migrate(&mut conn, None).unwrap();
+        assert_eq!(get_version(&conn).unwrap(), LATEST_VERSION);
+    }
+
+    #[test]
+    fn adopts_unversioned_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        migrate(&mut conn, None).unwrap();
+        assert_eq!(get_version(&conn).unwrap(), LATEST_VERSION);
+        let duration: u64 = conn
+            .query_row("SELECT duration FROM work", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(duration, 1500);
+        let task: String = conn
+            .query_row("SELECT name FROM tasks", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(task, "Old task");
+        assert!(column_exists(&conn, "work", "completed"));
+        assert!(column_exists(&conn, "projects", "budget_period"));
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", LATEST_VERSION + 1)
+            .unwrap();
+        match migrate(&mut conn, None) {
+            Err(MigrationError::TooNew { db_version }) => {
+                assert_eq!(db_version, LATEST_VERSION + 1)
+            }
+            other => panic!("expected TooNew, got {other:?}"),
+        }
+        assert_eq!(get_version(&conn).unwrap(), LATEST_VERSION + 1);
+    }
+
+    #[test]
+    fn backs_up_before_upgrading() {
+        let path = std::env::temp_dir().join(format!(
+            "hellowork_migrations_test_{}.db",
+            std::process::id()
+        ));
+        let backup = path.with_extension("db.v0.bak");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&backup);
+
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        migrate(&mut conn, Some(&path)).unwrap();
+        drop(conn);
+
+        let backup_conn = Connection::open(&backup).unwrap();
+        assert_eq!(get_version(&backup_conn).unwrap(), 0);
+        let projects: usize = backup_conn
+            .query_row("SELECT COUNT(*) FROM projects", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(projects, 1);
+        drop(backup_conn);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&backup).unwrap();
+    }
+}
@@ -1,46 +1,271 @@
 use crate::{
     config::{self, Config},
     db,
+    migrations::MigrationError,
     projects::Projects,
+    stats,
     todo_tasks::TodoTasks,
 };
+use chrono::NaiveDate;
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
 use std::{
+    fmt::Display,
     path::PathBuf,
     time::{Duration, SystemTime},
 };
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    #[default]
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Work => "Work",
+            Phase::ShortBreak => "Short Break",
+            Phase::LongBreak => "Long Break",
+        })
+    }
+}
+
 pub struct Pomo {
     pub session_length: u64,
+    pub phase: Phase,
+    // work sessions finished since the app started, decides when the long break comes
+    pub completed_sessions: u32,
     pub session_start: Option<SystemTime>,
     pub partial_start: Option<SystemTime>,
+    pub pause_start: Option<SystemTime>,
+    // time spent paused is excluded from both the countdown and the recorded durations
+    pub session_paused: Duration,
+    pub partial_paused: Duration,
+    last_heartbeat: SystemTime,
+    // set by the command line, the session keeps counting while no process is running
+    pub headless: bool,
+    // an unfinished session found in the DB on startup, waiting for the user to decide
+    pub recovered: Option<db::ActiveSession>,
     pub db: Connection,
     pub config_file_path: PathBuf,
     pub config: Config,
     pub projects: Projects,
     pub tasks: TodoTasks,
+    // daily goal streaks are an all-time query, so they are cached until the next change
+    streaks: Option<stats::Streaks>,
+    // to-do tasks completed in the stats tab's range, cached the same way
+    completed_task_count: Option<usize>,
+    // the day the cached stats were fetched for, None until the first fetch
+    cached_day: Option<NaiveDate>,
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn from_unix_secs(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
 }
 
+// how often the running session is written to the DB
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
 impl Pomo {
     pub fn is_running(&self) -> bool {
         self.session_start.is_some()
     }
+    pub fn is_break(&self) -> bool {
+        self.phase != Phase::Work
+    }
+    pub fn is_paused(&self) -> bool {
+        self.pause_start.is_some()
+    }
     pub fn init_session(&mut self) {
         self.session_start = Some(SystemTime::now());
         self.partial_start = Some(SystemTime::now());
+        self.pause_start = None;
+        self.session_paused = Duration::ZERO;
+        self.partial_paused = Duration::ZERO;
+        // a session left from the last run is kept as a partial one rather than thrown away
+        self.save_recovered_partial();
+        self.persist_session();
     }
-    pub fn cancel_session(&mut self) {
-        self.save_partial_session_if_enabled();
+    fn clear_session(&mut self) {
         self.session_start = None;
         self.partial_start = None;
+        self.pause_start = None;
+        self.session_paused = Duration::ZERO;
+        self.partial_paused = Duration::ZERO;
+        self.persist_session();
+    }
+    // only work sessions are kept, a lost break is not worth recovering
+    pub fn persist_session(&mut self) {
+        let now = SystemTime::now();
+        self.last_heartbeat = now;
+        match (self.session_start, self.partial_start) {
+            (Some(session_start), Some(partial_start)) if !self.is_break() => {
+                db::save_active_session(
+                    &self.db,
+                    &db::ActiveSession {
+                        session_start: unix_secs(session_start),
+                        partial_start: unix_secs(partial_start),
+                        pause_start: self.pause_start.map(unix_secs),
+                        session_paused: self.session_paused.as_secs(),
+                        partial_paused: self.partial_paused.as_secs(),
+                        completed_sessions: self.completed_sessions,
+                        project_id: self.projects.get_active(),
+                        task_id: self.tasks.get_current(),
+                        heartbeat: unix_secs(now),
+                        headless: self.headless,
+                    },
+                )
+                .expect("Saving active session into DB failed");
+            }
+            _ => {
+                db::delete_active_session(&self.db).expect("Deleting active session failed");
+            }
+        }
+    }
+    pub fn heartbeat(&mut self) {
+        let due = self
+            .last_heartbeat
+            .elapsed()
+            .map_or(true, |e| e >= HEARTBEAT_INTERVAL);
+        if self.is_running() && due {
+            self.persist_session();
+        }
+    }
+    pub fn resume_recovered(&mut self) {
+        if self.recovered.is_some() {
+            self.restore_recovered();
+            self.persist_session();
+        }
+    }
+    // loads the recovered session into memory without touching the DB
+    pub fn restore_recovered(&mut self) {
+        if let Some(r) = self.recovered.take() {
+            // The time the app was not running counts as paused, unless nothing was meant to run.
+            // A session paused back then keeps its pause_start, which covers the downtime already
+            let downtime = if r.headless || r.pause_start.is_some() {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(unix_secs(SystemTime::now()).saturating_sub(r.heartbeat))
+            };
+            self.phase = Phase::Work;
+            self.completed_sessions = r.completed_sessions;
+            self.session_start = Some(from_unix_secs(r.session_start));
+            self.partial_start = Some(from_unix_secs(r.partial_start));
+            self.pause_start = r.pause_start.map(from_unix_secs);
+            self.session_paused = Duration::from_secs(r.session_paused) + downtime;
+            self.partial_paused = Duration::from_secs(r.partial_paused) + downtime;
+            if r.project_id.is_some() {
+                self.projects.set_active(r.project_id);
+                self.tasks.switch_project(&self.db, r.project_id);
+            }
+            self.tasks.set_current(r.task_id);
+        }
+    }
+    pub fn save_recovered_partial(&mut self) {
+        if let Some(r) = self.recovered.take() {
+            // whatever happened after the last heartbeat is unknown, so it is left out
+            let end = if r.headless {
+                unix_secs(SystemTime::now())
+            } else {
+                r.heartbeat
+            };
+            let paused_at_heartbeat = r.pause_start.map_or(0, |p| end.saturating_sub(p));
+            let duration_secs = end
+                .saturating_sub(r.partial_start)
+                .saturating_sub(r.partial_paused)
+                .saturating_sub(paused_at_heartbeat);
+            if duration_secs > 0 {
+                db::add_work_session(
+                    &self.db,
+                    &db::WorkSession {
+                        time_start: r.partial_start,
+                        duration: duration_secs,
+                        project_id: r.project_id,
+                        task_id: r.task_id,
+                        completed: false,
+                    },
+                )
+                .expect("Recording recovered work session into DB failed");
+                eprintln!("Recovered session: {}, {duration_secs}", r.partial_start);
+                self.projects.fetch(&self.db);
+                self.tasks.fetch(&self.db);
+                self.refresh_streaks();
+            }
+            db::delete_active_session(&self.db).expect("Deleting active session failed");
+        }
+    }
+    pub fn discard_recovered(&mut self) {
+        if self.recovered.take().is_some() {
+            db::delete_active_session(&self.db).expect("Deleting active session failed");
+        }
+    }
+    pub fn cancel_session(&mut self) {
+        // cancelling a break skips it, nothing to record
+        if !self.is_break() {
+            self.save_partial_session_if_enabled();
+        }
+        self.phase = Phase::Work;
+        self.clear_session();
+    }
+    pub fn toggle_pause(&mut self) {
+        if !self.is_running() {
+            return;
+        }
+        if self.is_paused() {
+            let paused = self.current_pause();
+            self.session_paused += paused;
+            self.partial_paused += paused;
+            self.pause_start = None;
+        } else {
+            self.pause_start = Some(SystemTime::now());
+        }
+        self.persist_session();
+    }
+    fn current_pause(&self) -> Duration {
+        self.pause_start
+            .and_then(|p| p.elapsed().ok())
+            .unwrap_or_default()
+    }
+    // length of the current phase in seconds
+    pub fn phase_length(&self) -> u64 {
+        let in_min = match self.phase {
+            Phase::Work => return self.session_length,
+            Phase::ShortBreak => self.config.get_short_break_length(),
+            Phase::LongBreak => self.config.get_long_break_length(),
+        };
+        (in_min * 60.0) as u64
     }
     fn finish_session(&mut self) {
-        let already_recorded = (self
-            .partial_start
+        if self.is_break() {
+            self.finish_break();
+        } else {
+            self.finish_work();
+        }
+        crate::audio::play_audio(
+            self.config.work_end_audio.clone(),
+            self.config.work_end_audio_volume.unwrap_or(1.0),
+        );
+    }
+    fn finish_break(&mut self) {
+        // back to idle, the next work session is started by the user
+        self.phase = Phase::Work;
+        self.clear_session();
+    }
+    fn finish_work(&mut self) {
+        // pauses are excluded from both, so this is the worked time saved by partial sessions
+        let already_recorded = self
+            .session_elapsed()
             .unwrap()
-            .duration_since(self.session_start.unwrap()))
-        .unwrap()
-        .as_secs();
+            .saturating_sub(self.partial_elapsed().unwrap())
+            .as_secs();
 
         // saturating_sub goes to 0 if session_length is smaller than already_recorded
         // which might happen with config modifications
@@ -59,21 +284,30 @@ impl Pomo {
                 time_start: start_unix,
                 duration: duration_secs,
                 project_id: self.projects.get_active(),
+                task_id: self.tasks.get_current(),
+                completed: true,
             },
         )
         .expect("Recording work session into DB failed");
-        println!("Session: {start_unix}, {duration_secs}");
+        eprintln!("Session: {start_unix}, {duration_secs}");
         self.projects.fetch(&self.db); // refresh total work durations per project
+        self.tasks.fetch(&self.db);
+        self.refresh_streaks();
 
-        self.session_start = None;
-        self.partial_start = None;
-        crate::audio::play_audio(
-            self.config.work_end_audio.clone(),
-            self.config.work_end_audio_volume.unwrap_or(1.0),
-        );
+        // breaks start right away, they are not recorded in the work table
+        self.completed_sessions += 1;
+        self.phase = if self
+            .completed_sessions
+            .is_multiple_of(self.config.get_long_break_interval())
+        {
+            Phase::LongBreak
+        } else {
+            Phase::ShortBreak
+        };
+        self.init_session();
     }
     pub fn save_partial_session_if_enabled(&mut self) {
-        if !self.config.get_save_partial_sessions() {
+        if !self.config.get_save_partial_sessions() || self.is_break() {
             return;
         }
 
@@ -94,38 +328,117 @@ impl Pomo {
                     time_start: partial_start_unix,
                     duration: duration_secs,
                     project_id: self.projects.get_active(),
+                    task_id: self.tasks.get_current(),
+                    completed: false,
                 },
             )
             .expect("Recording partial work session into DB failed");
-            println!("Partial session: {partial_start_unix}, {duration_secs}");
+            eprintln!("Partial session: {partial_start_unix}, {duration_secs}");
             self.partial_start = Some(SystemTime::now());
+            self.partial_paused = Duration::ZERO;
+            if self.is_paused() {
+                // restart the ongoing pause along with the new partial
+                self.session_paused += self.current_pause();
+                self.pause_start = self.partial_start;
+            }
+            self.persist_session();
 
             self.projects.fetch(&self.db); // Refresh total project hours
+            self.tasks.fetch(&self.db);
+            self.refresh_streaks();
         }
     }
     pub fn check_finished(&mut self) {
         self.session_elapsed().map(|elapsed| {
-            if elapsed.as_secs() >= self.session_length {
+            if elapsed.as_secs() >= self.phase_length() {
                 self.finish_session();
             }
         });
     }
     pub fn session_elapsed(&self) -> Option<Duration> {
-        self.session_start.and_then(|s| s.elapsed().ok())
+        self.session_start.and_then(|s| s.elapsed().ok()).map(|e| {
+            e.saturating_sub(self.session_paused)
+                .saturating_sub(self.current_pause())
+        })
+    }
+    // Work the timer will record later as start and end unix seconds: the running work session,
+    // which has no end yet, and a recovered one waiting for an answer
+    pub fn unrecorded_work(&self) -> Vec<(u64, u64)> {
+        let running = self
+            .partial_start
+            .filter(|_| !self.is_break())
+            .map(|start| (unix_secs(start), u64::MAX));
+        let recovered = self.recovered.as_ref().map(|r| {
+            let end = if r.headless {
+                unix_secs(SystemTime::now())
+            } else {
+                r.heartbeat
+            };
+            (r.partial_start, end)
+        });
+        running.into_iter().chain(recovered).collect()
     }
     pub fn partial_elapsed(&self) -> Option<Duration> {
-        self.partial_start.and_then(|s| s.elapsed().ok())
+        self.partial_start.and_then(|s| s.elapsed().ok()).map(|e| {
+            e.saturating_sub(self.partial_paused)
+                .saturating_sub(self.current_pause())
+        })
     }
     pub fn countdown_string(&self) -> String {
         match self.session_elapsed() {
             Some(t) => {
                 let secs = t.as_secs();
-                let rem = self.session_length - secs;
+                let rem = self.phase_length().saturating_sub(secs);
                 format!("{:02}:{:02}", rem / 60, rem % 60)
             }
             None => "--:--".to_owned(),
         }
     }
+    pub fn phase_string(&self) -> String {
+        let phase = match self.phase {
+            Phase::Work => {
+                let interval = self.config.get_long_break_interval();
+                let nth = self.completed_sessions % interval + 1;
+                format!("{} {nth}/{interval}", self.phase)
+            }
+            _ => self.phase.to_string(),
+        };
+        if self.is_paused() {
+            phase + " (Paused)"
+        } else {
+            phase
+        }
+    }
+    pub fn switch_project(&mut self, id: usize) {
+        // If a session is active, save progress accrued for the old project
+        if self.is_running() {
+            self.save_partial_session_if_enabled();
+        }
+
+        self.projects.set_active(Some(id));
+        self.config
+            .set_last_active_project(id, &self.config_file_path);
+        self.tasks.switch_project(&self.db, Some(id));
+        if self.is_running() {
+            self.persist_session();
+        }
+    }
+    pub fn select_task(&mut self, id: Option<usize>) {
+        // time so far goes to the task picked before
+        if self.is_running() {
+            self.save_partial_session_if_enabled();
+        }
+        self.tasks.set_current(id);
+        if self.is_running() {
+            self.persist_session();
+        }
+    }
+    // set when the active project, or one of its parents, has used up its budget
+    pub fn budget_warning(&self) -> Option<String> {
+        self.projects
+            .get_active()
+            .and_then(|id| self.projects.budget_warning(id))
+    }
     pub fn change_session_length(&mut self, new_in_min: f64) {
         self.session_length = (new_in_min * 60.0) as u64;
         self.config.session_length = Some(new_in_min);
@@ -143,23 +456,99 @@ impl Pomo {
         self.config.work_end_audio_volume = work_end_audio_volume;
         self.config.write_config(&self.config_file_path);
     }
+    pub fn get_streaks(&self) -> Option<&stats::Streaks> {
+        self.streaks.as_ref()
+    }
+    // call after anything that changes the recorded work or the daily goal
+    pub fn fetch_streaks(&mut self) {
+        let offset = self.config.get_day_end_offset_hours();
+        self.cached_day = Some(stats::today(offset));
+        self.streaks = self
+            .config
+            .get_daily_goal()
+            .map(|goal| stats::streaks(&self.db, offset, &goal));
+    }
+    // the command line never shows the streaks, so only refresh once they were fetched
+    fn refresh_streaks(&mut self) {
+        if self.cached_day.is_some() {
+            self.fetch_streaks();
+        }
+    }
+    // first and last day shown in the stats tab
+    pub fn stats_days(&self) -> (NaiveDate, NaiveDate) {
+        self.config.get_stats_range().days(
+            self.config.get_stats_custom_span(),
+            self.config.get_day_end_offset_hours(),
+        )
+    }
+    pub fn get_completed_task_count(&self) -> Option<usize> {
+        self.completed_task_count
+    }
+    // call after changing the stats range or completing, reopening, renaming or deleting tasks
+    pub fn fetch_completed_task_count(&mut self) {
+        let offset = self.config.get_day_end_offset_hours();
+        let (first, last) = self.stats_days();
+        self.cached_day = Some(stats::today(offset));
+        self.completed_task_count =
+            Some(stats::completed_tasks(&self.db, offset, first, last).len());
+    }
+    // Today's progress starts over, the streak may break, budget periods may end and the stats
+    // range may move along with the day
+    pub fn check_day_rollover(&mut self) {
+        self.projects.check_period_rollover(&self.db);
+        let today = stats::today(self.config.get_day_end_offset_hours());
+        if self.cached_day.is_some_and(|day| day != today) {
+            self.fetch_streaks();
+            self.fetch_completed_task_count();
+        }
+    }
 }
 
-impl Default for Pomo {
-    fn default() -> Self {
+impl Pomo {
+    pub fn open() -> Result<Self, MigrationError> {
         let config_file_path = config::config_dir().join("config.toml");
         let config = config::Config::read(&config_file_path);
-        let conn = db::init_db(&config::config_dir().join("hellowork.db"));
-        let pomo = Self {
+        let conn = db::init_db(&config::config_dir().join("hellowork.db"))?;
+        let recovered =
+            db::get_active_session(&conn).expect("Failed to read active session from DB");
+        Ok(Self {
+            phase: Phase::Work,
+            completed_sessions: 0,
             session_start: None,
             partial_start: None,
+            pause_start: None,
+            session_paused: Duration::ZERO,
+            partial_paused: Duration::ZERO,
+            last_heartbeat: SystemTime::now(),
+            headless: false,
+            recovered,
             session_length: (config.session_length.unwrap_or(25.0) * 60.0) as u64,
-            projects: Projects::new(&conn, config.get_last_active_project()),
-            tasks: TodoTasks::new(&conn, None),
+            projects: Projects::new(
+                &conn,
+                config.get_last_active_project(),
+                config.get_day_end_offset_hours(),
+            ),
+            tasks: TodoTasks::new(&conn, None, config.get_day_end_offset_hours()),
             config_file_path,
             config,
             db: conn,
-        };
-        pomo
+            streaks: None,
+            completed_task_count: None,
+            cached_day: None,
+        })
+    }
+}
+
+impl Default for Pomo {
+    fn default() -> Self {
+        Self::open().unwrap_or_else(|e| {
+            // the console is hidden on Windows, so tell the user in a dialog before quitting
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_title("Hello Work")
+                .set_description(e.to_string())
+                .show();
+            panic!("{e}");
+        })
     }
 }
@@ -1,8 +1,65 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeDelta};
 use rusqlite::Connection;
 use std::fmt::Display;
 use std::iter;
 
-use crate::db;
+use crate::{db, stats};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BudgetPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl BudgetPeriod {
+    pub const ALL: [BudgetPeriod; 3] = [BudgetPeriod::Day, BudgetPeriod::Week, BudgetPeriod::Month];
+
+    // as stored in the projects table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Day => "day",
+            BudgetPeriod::Week => "week",
+            BudgetPeriod::Month => "month",
+        }
+    }
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == s)
+    }
+    // e.g. "this week"
+    pub fn current_name(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Day => "today",
+            BudgetPeriod::Week => "this week",
+            BudgetPeriod::Month => "this month",
+        }
+    }
+    // weeks start on Monday, the day ends config_offset_hours after midnight
+    pub fn current_start(&self, config_offset_hours: u32) -> DateTime<Local> {
+        let today = Local::now()
+            .checked_sub_signed(TimeDelta::hours((config_offset_hours % 24) as i64))
+            .unwrap()
+            .date_naive();
+        let first_day = match self {
+            BudgetPeriod::Day => today,
+            BudgetPeriod::Week => {
+                today - TimeDelta::days(today.weekday().num_days_from_monday() as i64)
+            }
+            BudgetPeriod::Month => today.with_day(1).unwrap(),
+        };
+        db::day_start(&first_day, config_offset_hours)
+    }
+}
+
+impl Display for BudgetPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BudgetPeriod::Day => "Daily",
+            BudgetPeriod::Week => "Weekly",
+            BudgetPeriod::Month => "Monthly",
+        })
+    }
+}
 
 #[derive(PartialEq, Clone, Debug, Default)]
 pub struct Project {
@@ -12,6 +69,10 @@ pub struct Project {
     pub parent: Option<usize>,
     pub children: Vec<usize>,
     pub total_hours: f32,
+    pub budget_hours: Option<f32>,
+    pub budget_period: Option<BudgetPeriod>,
+    // hours in the current budget period, sub-projects included
+    pub budget_used_hours: f32,
 }
 
 impl Display for Project {
@@ -20,11 +81,45 @@ impl Display for Project {
     }
 }
 
+impl Project {
+    // share of target_hours done, None when there is no target
+    pub fn target_progress(&self) -> Option<f32> {
+        self.target_hours
+            .filter(|target| *target > 0.0)
+            .map(|target| self.total_hours / target)
+    }
+    pub fn target_reached(&self) -> bool {
+        self.target_progress()
+            .is_some_and(|progress| progress >= 1.0)
+    }
+    // hours left in the current period, negative when over, None without a budget
+    pub fn budget_remaining(&self) -> Option<f32> {
+        self.budget_period
+            .and(self.budget_hours)
+            .map(|budget| budget - self.budget_used_hours)
+    }
+    // e.g. "2.5h left this week"
+    pub fn budget_string(&self) -> Option<String> {
+        let period = self.budget_period?;
+        self.budget_remaining().map(|remaining| {
+            let hours = (remaining.abs() * 10.0).round() / 10.0;
+            if remaining >= 0.0 {
+                format!("{hours}h left {}", period.current_name())
+            } else {
+                format!("{hours}h over {}", period.current_name())
+            }
+        })
+    }
+}
+
 // A struct for caching project data to minimize DB access
 pub struct Projects {
     projects: Vec<Project>,
     active: Option<usize>,
     edited: Option<Project>,
+    day_end_offset_hours: u32,
+    // budget periods start on a day boundary, so the budgets are stale once the day changes
+    fetched_day: NaiveDate,
 }
 
 fn recurse<'a>(
@@ -44,19 +139,40 @@ fn recurse<'a>(
 }
 
 impl Projects {
-    pub fn new(conn: &Connection, last_active: Option<usize>) -> Self {
+    pub fn new(conn: &Connection, last_active: Option<usize>, day_end_offset_hours: u32) -> Self {
         let mut p = Projects {
             projects: vec![],
             active: last_active,
             edited: None,
+            day_end_offset_hours,
+            fetched_day: stats::today(day_end_offset_hours),
         };
         p.fetch(conn);
         p
     }
     pub fn fetch(&mut self, conn: &Connection) {
+        self.fetched_day = stats::today(self.day_end_offset_hours);
         self.projects.truncate(0);
         self.projects
             .append(&mut db::get_projects(conn).expect("Failed to fetch projects"));
+        for p in self.projects.iter_mut() {
+            if let Some(period) = p.budget_period {
+                let start = period.current_start(self.day_end_offset_hours);
+                p.budget_used_hours = db::get_project_hours_since(conn, p.id, start.timestamp())
+                    .expect("Failed to fetch project budget");
+            }
+        }
+    }
+    // budget periods follow the day end offset
+    pub fn set_day_end_offset_hours(&mut self, day_end_offset_hours: u32, conn: &Connection) {
+        self.day_end_offset_hours = day_end_offset_hours;
+        self.fetch(conn);
+    }
+    // a new day may start a new budget period
+    pub fn check_period_rollover(&mut self, conn: &Connection) {
+        if self.fetched_day != stats::today(self.day_end_offset_hours) {
+            self.fetch(conn);
+        }
     }
     pub fn get_all_tree_style(&self) -> Vec<(usize, &Project)> {
         self.projects
@@ -65,11 +181,11 @@ impl Projects {
             .flat_map(|p| recurse(p, &self.projects, 0))
             .collect()
     }
-    /*pub fn get(&self, id: usize) -> Option<&Project> {
+    pub fn get(&self, id: usize) -> Option<&Project> {
         self.projects.iter().find(|p| p.id == id)
-    }*/
+    }
     pub fn add(&mut self, parent: Option<usize>, conn: &Connection) {
-        let id = db::add_project(conn, parent).expect("Failed to add project");
+        let id = db::add_project(conn, "", parent).expect("Failed to add project");
         self.fetch(conn);
         self.initiate_edit(Some(id));
     }
@@ -83,6 +199,30 @@ impl Projects {
         self.active
             .and_then(|x| self.projects.iter().find(|p| p.id == x))
     }
+    // sessions on a project count towards the budgets of its parents as well
+    pub fn budget_warning(&self, id: usize) -> Option<String> {
+        let mut project = self.get(id);
+        while let Some(p) = project {
+            if p.budget_remaining()
+                .is_some_and(|remaining| remaining <= 0.0)
+            {
+                return Some(format!(
+                    "{} budget for {} is used up ({:.1} / {} hours)",
+                    p.budget_period.unwrap(),
+                    p.name,
+                    p.budget_used_hours,
+                    p.budget_hours.unwrap_or_default()
+                ));
+            }
+            project = p.parent.and_then(|parent| self.get(parent));
+        }
+        None
+    }
+    pub fn find_by_name(&self, name: &str) -> Option<&Project> {
+        self.projects
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
     pub fn initiate_edit(&mut self, id: Option<usize>) {
         self.edited = id
             .and_then(|id| self.projects.iter().find(|p| p.id == id))
@@ -106,6 +246,25 @@ impl Projects {
             edited.name = name;
         }
     }
+    pub fn set_edited_target_hours(&mut self, target_hours: Option<f32>) {
+        if let Some(edited) = self.edited.as_mut() {
+            edited.target_hours = target_hours;
+        }
+    }
+    pub fn set_edited_budget_hours(&mut self, budget_hours: Option<f32>) {
+        if let Some(edited) = self.edited.as_mut() {
+            edited.budget_hours = budget_hours;
+            // weekly unless picked otherwise
+            if edited.budget_period.is_none() {
+                edited.budget_period = Some(BudgetPeriod::Week);
+            }
+        }
+    }
+    pub fn set_edited_budget_period(&mut self, budget_period: BudgetPeriod) {
+        if let Some(edited) = self.edited.as_mut() {
+            edited.budget_period = Some(budget_period);
+        }
+    }
     pub fn archive_edited_item(&mut self, conn: &Connection) {
         if let Some(edited) = self.edited.as_ref() {
             // archive children too when parent is archived
@@ -0,0 +1,271 @@
+use chrono::NaiveDate;
+use plotters::prelude::{IntoDrawingArea, SVGBackend};
+use rusqlite::Connection;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{
+    charts,
+    config::Config,
+    db,
+    stats::{self, StatsView},
+    util::local_time,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+// a project with its hours in the report, depth 0 for top-level projects
+struct ProjectLine {
+    depth: usize,
+    name: String,
+    hours: f32,
+}
+
+fn project_lines(projects: &[db::ProjectHours]) -> Vec<ProjectLine> {
+    fn recurse(
+        parent: Option<usize>,
+        depth: usize,
+        projects: &[db::ProjectHours],
+        lines: &mut Vec<ProjectLine>,
+    ) {
+        let mut children: Vec<&db::ProjectHours> =
+            projects.iter().filter(|p| p.parent == parent).collect();
+        children.sort_by(|a, b| b.hours.total_cmp(&a.hours));
+        for p in children {
+            lines.push(ProjectLine {
+                depth,
+                name: if p.archived {
+                    format!("{} (archived)", p.name)
+                } else {
+                    p.name.clone()
+                },
+                hours: p.hours,
+            });
+            recurse(Some(p.id), depth + 1, projects, lines);
+        }
+    }
+    let mut lines = vec![];
+    recurse(None, 0, projects, &mut lines);
+    lines
+}
+
+fn round(hours: f32) -> f32 {
+    (hours * 10.0).round() / 10.0
+}
+
+fn markdown_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            let escaped = matches!(c, '\\' | '*' | '_' | '[' | ']' | '#' | '`' | '<' | '>');
+            escaped.then_some('\\').into_iter().chain([c])
+        })
+        .collect()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// the day a task was completed, then its name
+fn task_line(task: &db::CompletedTask) -> String {
+    format!(
+        "{}: {}",
+        local_time(task.completed_at).format("%b %-d"),
+        task.name
+    )
+}
+
+// the total chart of the stats tab
+fn chart_svg(
+    conn: &Connection,
+    config: &Config,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<String, String> {
+    let spec = charts::ChartSpec {
+        view: StatsView::Total,
+        first: from,
+        last: to,
+        breakdown_parent: None,
+    };
+    let mut svg = String::new();
+    charts::render(
+        &SVGBackend::with_string(&mut svg, (800, 400)).into_drawing_area(),
+        conn,
+        config,
+        &spec,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(svg)
+}
+
+// Writes a report of the days from..=to. The Markdown report links the chart as chart_file
+// when one is given, otherwise the chart goes inline as in the HTML report
+pub fn write_report(
+    conn: &Connection,
+    config: &Config,
+    from: NaiveDate,
+    to: NaiveDate,
+    format: Format,
+    chart_file: Option<&Path>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let config_offset_hours = config.get_day_end_offset_hours();
+    let start = db::day_start(&from, config_offset_hours).timestamp();
+    let end = db::day_start(&to.succ_opt().unwrap(), config_offset_hours).timestamp();
+    let days =
+        db::get_work_per_day(conn, start, end, config_offset_hours).map_err(io::Error::other)?;
+    let projects = db::get_project_hours_between(conn, start, end).map_err(io::Error::other)?;
+    let svg = chart_svg(conn, config, from, to).map_err(io::Error::other)?;
+    let tasks = stats::completed_tasks(conn, config_offset_hours, from, to);
+
+    let total_hours: f32 = days.iter().map(|d| d.hours).sum();
+    let pomodoros: u32 = days.iter().map(|d| d.sessions).sum();
+    let lines = project_lines(&projects);
+    let top_level_hours: f32 = lines.iter().filter(|l| l.depth == 0).map(|l| l.hours).sum();
+    let no_project_hours = total_hours - top_level_hours;
+
+    let span = format!(
+        "{} - {}",
+        from.format("%b %-d, %Y"),
+        to.format("%b %-d, %Y")
+    );
+    let mut summary = format!(
+        "{} hours worked on {} days, {} completed pomodoros, {} completed tasks",
+        round(total_hours),
+        days.len(),
+        pomodoros,
+        tasks.len()
+    );
+    if !days.is_empty() {
+        let _ = write!(
+            summary,
+            ", {} hours per day worked",
+            round(total_hours / days.len() as f32)
+        );
+    }
+
+    match format {
+        Format::Markdown => {
+            writeln!(out, "# Hello Work Report\n\n{span}\n\n{summary}\n")?;
+            writeln!(out, "## Hours\n")?;
+            match chart_file {
+                Some(path) => {
+                    std::fs::write(path, &svg)?;
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    writeln!(out, "![Hours]({})\n", name.replace(' ', "%20"))?;
+                }
+                None => writeln!(out, "{svg}\n")?,
+            }
+            writeln!(out, "## Projects\n")?;
+            for line in &lines {
+                writeln!(
+                    out,
+                    "{}- {}: {} hours",
+                    "  ".repeat(line.depth),
+                    markdown_escape(&line.name),
+                    round(line.hours)
+                )?;
+            }
+            if no_project_hours >= 0.05 {
+                writeln!(out, "- No project: {} hours", round(no_project_hours))?;
+            }
+            if !tasks.is_empty() {
+                writeln!(out, "\n## Completed Tasks\n")?;
+            }
+            for task in &tasks {
+                writeln!(
+                    out,
+                    "- {}{}",
+                    markdown_escape(&task_line(task)),
+                    task.project_name
+                        .as_ref()
+                        .map(|p| format!(" ({})", markdown_escape(p)))
+                        .unwrap_or_default()
+                )?;
+            }
+        }
+        Format::Html => {
+            writeln!(
+                out,
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                <title>Hello Work Report {span}</title>\n</head>\n<body>"
+            )?;
+            writeln!(
+                out,
+                "<h1>Hello Work Report</h1>\n<p>{span}</p>\n<p>{summary}</p>"
+            )?;
+            writeln!(out, "<h2>Hours</h2>\n{svg}")?;
+            writeln!(out, "<h2>Projects</h2>\n<ul>")?;
+            // nested lists follow the depth of each line
+            let mut depth = 0;
+            for (i, line) in lines.iter().enumerate() {
+                if i > 0 {
+                    if line.depth > depth {
+                        writeln!(out, "<ul>")?;
+                    } else {
+                        writeln!(out, "</li>")?;
+                        for _ in line.depth..depth {
+                            writeln!(out, "</ul></li>")?;
+                        }
+                    }
+                }
+                depth = line.depth;
+                write!(
+                    out,
+                    "<li>{}: {} hours",
+                    html_escape(&line.name),
+                    round(line.hours)
+                )?;
+            }
+            if !lines.is_empty() {
+                writeln!(out, "</li>")?;
+                for _ in 0..depth {
+                    writeln!(out, "</ul></li>")?;
+                }
+            }
+            if no_project_hours >= 0.05 {
+                writeln!(
+                    out,
+                    "<li>No project: {} hours</li>",
+                    round(no_project_hours)
+                )?;
+            }
+            writeln!(out, "</ul>")?;
+            if !tasks.is_empty() {
+                writeln!(out, "<h2>Completed Tasks</h2>\n<ul>")?;
+                for task in &tasks {
+                    writeln!(
+                        out,
+                        "<li>{}{}</li>",
+                        html_escape(&task_line(task)),
+                        task.project_name
+                            .as_ref()
+                            .map(|p| format!(" ({})", html_escape(p)))
+                            .unwrap_or_default()
+                    )?;
+                }
+                writeln!(out, "</ul>")?;
+            }
+            writeln!(out, "</body>\n</html>")?;
+        }
+    }
+    Ok(())
+}
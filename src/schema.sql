@@ -24,6 +24,22 @@ CREATE TABLE IF NOT EXISTS tasks (
         REFERENCES projects (id)
 );
 
+-- the session currently running, kept so it survives crashes and restarts
+-- times are unix seconds, paused durations are seconds
+CREATE TABLE IF NOT EXISTS active_session (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    session_start INTEGER NOT NULL,
+    partial_start INTEGER NOT NULL,
+    pause_start INTEGER,
+    session_paused INTEGER NOT NULL DEFAULT 0,
+    partial_paused INTEGER NOT NULL DEFAULT 0,
+    completed_sessions INTEGER NOT NULL DEFAULT 0,
+    project_id INTEGER,
+    heartbeat INTEGER NOT NULL,
+    FOREIGN KEY (project_id)
+        REFERENCES projects (id)
+);
+
 CREATE VIEW IF NOT EXISTS work_totals AS 
 	SELECT
 		project_id,
@@ -1,23 +1,554 @@
-use chrono::{Local, NaiveDate, TimeDelta};
+use chrono::{Datelike, Local, NaiveDate, TimeDelta, Timelike};
 use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::iter;
 
-use crate::db;
+use crate::{
+    config::{DailyGoal, GoalUnit},
+    db,
+    util::{local_time, session_day},
+};
 
-pub fn last_week_chart(conn: &Connection, config_offset_hours: u32) -> Vec<(NaiveDate, f32)> {
-    //let offset = FixedOffset::east_opt((config_offset_hours * 60 * 60) % (24 * 60 * 60)).unwrap();
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StatsView {
+    #[default]
+    Total,
+    ByProject,
+    Heatmap,
+    HourOfDay,
+    Weekday,
+    Estimates,
+}
+
+impl StatsView {
+    pub const ALL: [StatsView; 6] = [
+        StatsView::Total,
+        StatsView::ByProject,
+        StatsView::Heatmap,
+        StatsView::HourOfDay,
+        StatsView::Weekday,
+        StatsView::Estimates,
+    ];
+}
+
+impl Display for StatsView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StatsView::Total => "Total",
+            StatsView::ByProject => "By Project",
+            StatsView::Heatmap => "Heatmap",
+            StatsView::HourOfDay => "Hour of Day",
+            StatsView::Weekday => "Weekday",
+            StatsView::Estimates => "Estimates",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsRange {
+    ThisWeek,
+    #[default]
+    Last7Days,
+    ThisMonth,
+    Last30Days,
+    ThisYear,
+    Custom,
+}
+
+impl StatsRange {
+    pub const ALL: [StatsRange; 6] = [
+        StatsRange::ThisWeek,
+        StatsRange::Last7Days,
+        StatsRange::ThisMonth,
+        StatsRange::Last30Days,
+        StatsRange::ThisYear,
+        StatsRange::Custom,
+    ];
+
+    // first and last day, inclusive. Custom falls back to the last 7 days without a span
+    pub fn days(
+        &self,
+        custom: Option<(NaiveDate, NaiveDate)>,
+        config_offset_hours: u32,
+    ) -> (NaiveDate, NaiveDate) {
+        let today = today(config_offset_hours);
+        match (self, custom) {
+            (StatsRange::ThisWeek, _) => (
+                today - TimeDelta::days(today.weekday().num_days_from_monday() as i64),
+                today,
+            ),
+            (StatsRange::ThisMonth, _) => (today.with_day(1).unwrap(), today),
+            (StatsRange::Last30Days, _) => (today - TimeDelta::days(29), today),
+            (StatsRange::ThisYear, _) => (today.with_ordinal(1).unwrap(), today),
+            (StatsRange::Custom, Some(span)) => span,
+            (StatsRange::Last7Days | StatsRange::Custom, _) => (today - TimeDelta::days(6), today),
+        }
+    }
+}
+
+impl Display for StatsRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            StatsRange::ThisWeek => "This Week",
+            StatsRange::Last7Days => "Last 7 Days",
+            StatsRange::ThisMonth => "This Month",
+            StatsRange::Last30Days => "Last 30 Days",
+            StatsRange::ThisYear => "This Year",
+            StatsRange::Custom => "Custom",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bucket {
+    Day,
+    Week,
+    Month,
+}
 
-    let mut day = Local::now()
+impl Bucket {
+    // short axis label for the bucket starting on day
+    pub fn label(&self, day: &NaiveDate) -> String {
+        match self {
+            Bucket::Day | Bucket::Week => format!("{}-{}", day.month(), day.day()),
+            Bucket::Month => day.format("%b").to_string(),
+        }
+    }
+}
+
+// the day that is going on, which ends config_offset_hours after midnight
+pub fn today(config_offset_hours: u32) -> NaiveDate {
+    Local::now()
         .checked_sub_signed(TimeDelta::hours((config_offset_hours % 24) as i64))
         .unwrap()
-        .date_naive();
+        .date_naive()
+}
+
+// Splits first..=last into days, weeks or months depending on its length, weeks start on Monday.
+// Returns the first day of each bucket, the first and last buckets may be cut short by the range
+pub fn buckets(first: NaiveDate, last: NaiveDate) -> (Bucket, Vec<NaiveDate>) {
+    let span = (last - first).num_days() + 1;
+    let bucket = if span <= 31 {
+        Bucket::Day
+    } else if span <= 183 {
+        Bucket::Week
+    } else {
+        Bucket::Month
+    };
+    let mut starts = vec![first];
+    let mut day = first;
+    loop {
+        day = match bucket {
+            Bucket::Day => day + TimeDelta::days(1),
+            Bucket::Week => day + TimeDelta::days(7 - day.weekday().num_days_from_monday() as i64),
+            Bucket::Month => (day.with_day(1).unwrap() + TimeDelta::days(32))
+                .with_day(1)
+                .unwrap(),
+        };
+        if day > last {
+            break;
+        }
+        starts.push(day);
+    }
+    (bucket, starts)
+}
+
+// unix time spans of each bucket
+fn bucket_spans(
+    starts: &[NaiveDate],
+    last: NaiveDate,
+    config_offset_hours: u32,
+) -> Vec<(i64, i64)> {
+    let day_after_last = last.succ_opt().unwrap();
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain(iter::once(&day_after_last)))
+        .map(|(start, end)| {
+            (
+                db::day_start(start, config_offset_hours).timestamp(),
+                db::day_start(end, config_offset_hours).timestamp(),
+            )
+        })
+        .collect()
+}
+
+// hours per bucket of first..=last, oldest first
+pub fn chart(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> (Bucket, Vec<(NaiveDate, f32)>) {
+    let (bucket, starts) = buckets(first, last);
+    let data = bucket_spans(&starts, last, config_offset_hours)
+        .into_iter()
+        .zip(starts)
+        .map(|((start, end), day)| (day, db::get_work_hours_between(conn, start, end).unwrap()))
+        .collect();
+    (bucket, data)
+}
+
+pub struct ProjectSeries {
+    // None for sessions without a project, or on the broken down project itself
+    pub project_id: Option<usize>,
+    pub name: String,
+    pub hours: Vec<f32>,
+}
+
+// hours per bucket of first..=last, oldest first, split by top-level project or by the
+// children of parent. Series come with the most hours first
+pub fn by_project(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+    parent: Option<usize>,
+) -> (Bucket, Vec<NaiveDate>, Vec<ProjectSeries>) {
+    let (bucket, starts) = buckets(first, last);
+    let spans = bucket_spans(&starts, last, config_offset_hours);
+
+    let mut series: Vec<ProjectSeries> = vec![];
+    for (i, (start, end)) in spans.into_iter().enumerate() {
+        let groups = db::get_work_hours_by_group(conn, start, end, parent).unwrap();
+        for group in groups {
+            let index = match series.iter().position(|s| s.project_id == group.project_id) {
+                Some(index) => index,
+                None => {
+                    series.push(ProjectSeries {
+                        project_id: group.project_id,
+                        name: group.name.unwrap_or("No project".to_owned()),
+                        hours: vec![0.0; starts.len()],
+                    });
+                    series.len() - 1
+                }
+            };
+            series[index].hours[i] = group.hours;
+        }
+    }
+    let total = |s: &ProjectSeries| s.hours.iter().sum::<f32>();
+    series.sort_by(|a, b| total(b).total_cmp(&total(a)));
+    (bucket, starts, series)
+}
+
+// Hours per day for the heatmap, starting on the Monday 52 weeks before the current week
+// and ending today. Days without work are left out
+pub fn year_heatmap(
+    conn: &Connection,
+    config_offset_hours: u32,
+) -> (NaiveDate, HashMap<NaiveDate, f32>) {
+    let today = today(config_offset_hours);
+    let first = today
+        - TimeDelta::weeks(52)
+        - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+    let start = db::day_start(&first, config_offset_hours);
+    let end = db::day_start(&today.succ_opt().unwrap(), config_offset_hours);
+    let days = db::get_work_per_day(
+        conn,
+        start.timestamp(),
+        end.timestamp(),
+        config_offset_hours,
+    )
+    .unwrap();
+    (first, days.into_iter().map(|d| (d.day, d.hours)).collect())
+}
+
+pub struct Streaks {
+    // towards the goal, in hours or pomodoros
+    pub today: f32,
+    // days in a row meeting the goal, today only counts once it is met
+    pub current: u32,
+    pub longest: u32,
+}
+
+pub fn streaks(conn: &Connection, config_offset_hours: u32, goal: &DailyGoal) -> Streaks {
+    let today = today(config_offset_hours);
+    let end = db::day_start(&today.succ_opt().unwrap(), config_offset_hours);
+    let days = db::get_work_per_day(conn, 0, end.timestamp(), config_offset_hours).unwrap();
+    count_streaks(&days, today, goal)
+}
+
+// days in order, up to today
+fn count_streaks(days: &[db::DayWork], today: NaiveDate, goal: &DailyGoal) -> Streaks {
+    let amount = |d: &db::DayWork| match goal.unit {
+        GoalUnit::Hours => d.hours,
+        GoalUnit::Pomodoros => d.sessions as f32,
+    };
+
+    let mut streaks = Streaks {
+        today: days
+            .iter()
+            .find(|d| d.day == today)
+            .map(amount)
+            .unwrap_or_default(),
+        current: 0,
+        longest: 0,
+    };
+    // days come in order, a gap in the dates breaks the run
+    let mut run = 0;
+    let mut last_met: Option<NaiveDate> = None;
+    for d in days.iter().filter(|d| amount(d) >= goal.amount) {
+        run = match last_met {
+            Some(last) if last.succ_opt() == Some(d.day) => run + 1,
+            _ => 1,
+        };
+        last_met = Some(d.day);
+        streaks.longest = streaks.longest.max(run);
+    }
+    // the streak is still alive until today is over
+    if last_met.is_some_and(|last| last == today || last.succ_opt() == Some(today)) {
+        streaks.current = run;
+    }
+    streaks
+}
+
+fn sessions_between(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Vec<db::WorkSession> {
+    let start = db::day_start(&first, config_offset_hours);
+    let end = db::day_start(&last.succ_opt().unwrap(), config_offset_hours);
+    db::get_work_sessions(conn, start.timestamp(), end.timestamp()).unwrap()
+}
+
+// hours worked in each hour of the day from 0 to 23, local time
+pub fn hour_of_day(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> [f32; 24] {
+    let mut hours = [0.0; 24];
+    for session in sessions_between(conn, config_offset_hours, first, last) {
+        split_by_hour(&mut hours, session.time_start, session.duration);
+    }
+    hours
+}
+
+// Adds the session to the hours it covers, stepping by the seconds left in each hour so
+// clock changes don't matter
+fn split_by_hour(hours: &mut [f32; 24], time_start: u64, duration: u64) {
+    let mut time = time_start;
+    let end = time_start + duration;
+    while time < end {
+        let local = local_time(time);
+        let into_hour = (local.minute() * 60 + local.second()) as u64;
+        let step = (60 * 60 - into_hour).min(end - time);
+        hours[local.hour() as usize] += step as f32 / (60.0 * 60.0);
+        time += step;
+    }
+}
+
+// hours worked on each weekday, Monday first. Sessions count towards the day they started
+pub fn weekdays(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> [f32; 7] {
+    let mut hours = [0.0; 7];
+    for session in sessions_between(conn, config_offset_hours, first, last) {
+        let weekday = session_day(session.time_start, config_offset_hours).weekday();
+        hours[weekday.num_days_from_monday() as usize] += session.duration as f32 / (60.0 * 60.0);
+    }
+    hours
+}
+
+// to-do tasks checked off in first..=last, oldest first
+pub fn completed_tasks(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> Vec<db::CompletedTask> {
+    let start = db::day_start(&first, config_offset_hours);
+    let end = db::day_start(&last.succ_opt().unwrap(), config_offset_hours);
+    db::get_completed_tasks(conn, start.timestamp(), end.timestamp()).unwrap()
+}
+
+pub struct EstimateSeries {
+    pub project_id: Option<usize>,
+    pub name: String,
+    // pomodoros of the estimated tasks completed in each bucket
+    pub estimated: Vec<u32>,
+    pub actual: Vec<u32>,
+}
+
+impl EstimateSeries {
+    // actual pomodoros as a percentage of the estimate, None for buckets without estimates
+    pub fn accuracy(&self, i: usize) -> Option<f32> {
+        (self.estimated[i] > 0).then(|| self.actual[i] as f32 / self.estimated[i] as f32 * 100.0)
+    }
+}
+
+// estimated against actual pomodoros of the tasks completed in first..=last, per project
+pub fn estimates(
+    conn: &Connection,
+    config_offset_hours: u32,
+    first: NaiveDate,
+    last: NaiveDate,
+) -> (Bucket, Vec<NaiveDate>, Vec<EstimateSeries>) {
+    let (bucket, starts) = buckets(first, last);
+    let mut series: Vec<EstimateSeries> = vec![];
+    let tasks = completed_tasks(conn, config_offset_hours, first, last);
+    for task in tasks {
+        let Some(estimate) = task.estimate else {
+            continue;
+        };
+        let day = session_day(task.completed_at, config_offset_hours);
+        let i = starts
+            .partition_point(|start| *start <= day)
+            .saturating_sub(1);
+        let index = match series.iter().position(|s| s.project_id == task.project_id) {
+            Some(index) => index,
+            None => {
+                series.push(EstimateSeries {
+                    project_id: task.project_id,
+                    name: task.project_name.unwrap_or("No project".to_owned()),
+                    estimated: vec![0; starts.len()],
+                    actual: vec![0; starts.len()],
+                });
+                series.len() - 1
+            }
+        };
+        series[index].estimated[i] += estimate;
+        series[index].actual[i] += task.pomodoros;
+    }
+    (bucket, starts, series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    // days with (hours, pomodoros)
+    fn days(work: &[(&str, f32, u32)]) -> Vec<db::DayWork> {
+        work.iter()
+            .map(|(day, hours, sessions)| db::DayWork {
+                day: date(day),
+                hours: *hours,
+                sessions: *sessions,
+            })
+            .collect()
+    }
+
+    const FOUR_HOURS: DailyGoal = DailyGoal {
+        amount: 4.0,
+        unit: GoalUnit::Hours,
+    };
+
+    #[test]
+    fn missed_day_ends_the_streak() {
+        let work = days(&[
+            ("2025-03-01", 4.0, 8),
+            ("2025-03-02", 5.0, 10),
+            ("2025-03-03", 4.5, 9),
+            // worked, but not enough
+            ("2025-03-04", 2.0, 4),
+            ("2025-03-05", 4.0, 8),
+            ("2025-03-06", 6.0, 12),
+        ]);
+        let streaks = count_streaks(&work, date("2025-03-06"), &FOUR_HOURS);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.longest, 3);
+        assert_eq!(streaks.today, 6.0);
+    }
+
+    #[test]
+    fn day_without_work_ends_the_streak() {
+        let work = days(&[
+            ("2025-03-01", 4.0, 8),
+            ("2025-03-02", 4.0, 8),
+            ("2025-03-04", 4.0, 8),
+        ]);
+        let streaks = count_streaks(&work, date("2025-03-04"), &FOUR_HOURS);
+        assert_eq!(streaks.current, 1);
+        assert_eq!(streaks.longest, 2);
+    }
+
+    #[test]
+    fn unfinished_today_keeps_the_streak_without_counting() {
+        let work = days(&[
+            ("2025-03-01", 4.0, 8),
+            ("2025-03-02", 4.0, 8),
+            ("2025-03-03", 1.5, 3),
+        ]);
+        let streaks = count_streaks(&work, date("2025-03-03"), &FOUR_HOURS);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.longest, 2);
+        assert_eq!(streaks.today, 1.5);
+        // nothing at all yet today
+        let streaks = count_streaks(&work[..2], date("2025-03-03"), &FOUR_HOURS);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(streaks.today, 0.0);
+        // a whole day missed in between
+        let streaks = count_streaks(&work[..2], date("2025-03-04"), &FOUR_HOURS);
+        assert_eq!(streaks.current, 0);
+        assert_eq!(streaks.longest, 2);
+    }
+
+    #[test]
+    fn pomodoro_goal_counts_sessions() {
+        let goal = DailyGoal {
+            amount: 8.0,
+            unit: GoalUnit::Pomodoros,
+        };
+        let work = days(&[("2025-03-01", 1.0, 8), ("2025-03-02", 6.0, 7)]);
+        let streaks = count_streaks(&work, date("2025-03-02"), &goal);
+        assert_eq!(streaks.current, 1);
+        assert_eq!(streaks.longest, 1);
+        assert_eq!(streaks.today, 7.0);
+    }
+
+    // mid-January, when no time zone changes its clocks
+    fn unix(time: &str) -> u64 {
+        date("2025-01-15")
+            .and_time(chrono::NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+            .and_local_timezone(Local)
+            .unwrap()
+            .timestamp() as u64
+    }
+
+    fn split(sessions: &[(&str, u64)]) -> [f32; 24] {
+        let mut hours = [0.0; 24];
+        for (start, minutes) in sessions {
+            split_by_hour(&mut hours, unix(start), minutes * 60);
+        }
+        hours
+    }
+
+    #[test]
+    fn session_within_an_hour_stays_there() {
+        let hours = split(&[("09:10", 25)]);
+        assert!((hours[9] - 25.0 / 60.0).abs() < 0.001);
+        assert_eq!(hours.iter().sum::<f32>(), hours[9]);
+    }
+
+    #[test]
+    fn session_across_hours_is_split() {
+        let hours = split(&[("09:45", 90)]);
+        assert!((hours[9] - 0.25).abs() < 0.001);
+        assert!((hours[10] - 1.0).abs() < 0.001);
+        assert!((hours[11] - 0.25).abs() < 0.001);
+        // ending right on the hour adds nothing to the next one
+        let hours = split(&[("13:30", 30)]);
+        assert!((hours[13] - 0.5).abs() < 0.001);
+        assert_eq!(hours[14], 0.0);
+    }
 
-    let mut stats = vec![];
-    for _ in 0..7 {
-        stats.push((
-            day,
-            db::get_work_hours_for_day(conn, &day, config_offset_hours).unwrap(),
-        ));
-        day = day.pred_opt().unwrap();
+    #[test]
+    fn session_across_midnight_wraps_around() {
+        let hours = split(&[("23:40", 40), ("23:50", 5)]);
+        assert!((hours[23] - 25.0 / 60.0).abs() < 0.001);
+        assert!((hours[0] - 20.0 / 60.0).abs() < 0.001);
+        assert!((hours.iter().sum::<f32>() - 0.75).abs() < 0.001);
     }
-    stats
 }
@@ -1,12 +1,231 @@
+use chrono::{Datelike, Local, Months, NaiveDate, TimeDelta, Weekday};
 use rusqlite::Connection;
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::iter;
 
-use crate::db;
+use crate::{db, stats, util};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Priority {
+    // as stored in the tasks table, higher sorts first
+    pub fn value(&self) -> i64 {
+        match self {
+            Priority::Low => -1,
+            Priority::Normal => 0,
+            Priority::High => 1,
+        }
+    }
+    pub fn from_value(value: i64) -> Self {
+        match value {
+            ..0 => Priority::Low,
+            0 => Priority::Normal,
+            _ => Priority::High,
+        }
+    }
+    // the next one when clicking through them
+    pub fn cycle(&self) -> Self {
+        match self {
+            Priority::Normal => Priority::High,
+            Priority::High => Priority::Low,
+            Priority::Low => Priority::Normal,
+        }
+    }
+    // a short mark for lists
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Priority::Low => "↓",
+            Priority::Normal => "·",
+            Priority::High => "!",
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Priority::Low => "Low",
+            Priority::Normal => "Normal",
+            Priority::High => "High",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Recurrence {
+    Daily,
+    Weekdays,
+    Weekly(Weekday),
+    // on this day of the month, the last day of shorter months
+    Monthly { day: u32 },
+}
+
+// the day of the month, or the last day of the month when it is shorter
+fn day_in_month(date: NaiveDate, day: u32) -> NaiveDate {
+    let first = date.with_day(1).unwrap();
+    let last_day = (first.checked_add_months(Months::new(1)).unwrap() - TimeDelta::days(1)).day();
+    first.with_day(day.min(last_day)).unwrap()
+}
+
+impl Recurrence {
+    const WEEKDAYS: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    // the choices for a task, monthly ones recur on the day of date
+    pub fn options(date: NaiveDate) -> Vec<Recurrence> {
+        [Recurrence::Daily, Recurrence::Weekdays]
+            .into_iter()
+            .chain(Self::WEEKDAYS.map(Recurrence::Weekly))
+            .chain([Recurrence::Monthly { day: date.day() }])
+            .collect()
+    }
+
+    // as stored in the tasks table
+    pub fn as_string(&self) -> String {
+        match self {
+            Recurrence::Daily => "daily".to_owned(),
+            Recurrence::Weekdays => "weekdays".to_owned(),
+            Recurrence::Weekly(day) => format!("weekly {}", day.to_string().to_lowercase()),
+            Recurrence::Monthly { day } => format!("monthly {day}"),
+        }
+    }
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.split_once(' ') {
+            Some(("monthly", day)) => day
+                .parse()
+                .ok()
+                .filter(|day| (1..=31).contains(day))
+                .map(|day| Recurrence::Monthly { day }),
+            _ => [Recurrence::Daily, Recurrence::Weekdays]
+                .into_iter()
+                .chain(Self::WEEKDAYS.map(Recurrence::Weekly))
+                .find(|r| r.as_string() == s),
+        }
+    }
+    // the first day after date the task recurs on
+    fn next_after(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Recurrence::Daily => date + TimeDelta::days(1),
+            Recurrence::Weekdays => match date.weekday() {
+                Weekday::Fri => date + TimeDelta::days(3),
+                Weekday::Sat => date + TimeDelta::days(2),
+                _ => date + TimeDelta::days(1),
+            },
+            Recurrence::Weekly(day) => {
+                let days = (day.num_days_from_monday() + 6 - date.weekday().num_days_from_monday())
+                    % 7
+                    + 1;
+                date + TimeDelta::days(days as i64)
+            }
+            // from the day itself rather than date, which may have been a shorter month's last
+            Recurrence::Monthly { day } => {
+                let this_month = day_in_month(date, *day);
+                if this_month > date {
+                    this_month
+                } else {
+                    day_in_month(date.checked_add_months(Months::new(1)).unwrap(), *day)
+                }
+            }
+        }
+    }
+    // the due date of the task repeating one due on due, always after today so
+    // missed occurrences are skipped
+    pub fn next_due(&self, due: Option<NaiveDate>, today: NaiveDate) -> NaiveDate {
+        let mut next = self.next_after(due.unwrap_or(today));
+        while next <= today {
+            next = self.next_after(next);
+        }
+        next
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => f.write_str("Daily"),
+            Recurrence::Weekdays => f.write_str("Every weekday"),
+            Recurrence::Weekly(day) => write!(
+                f,
+                "Weekly on {}",
+                match day {
+                    Weekday::Mon => "Monday",
+                    Weekday::Tue => "Tuesday",
+                    Weekday::Wed => "Wednesday",
+                    Weekday::Thu => "Thursday",
+                    Weekday::Fri => "Friday",
+                    Weekday::Sat => "Saturday",
+                    Weekday::Sun => "Sunday",
+                }
+            ),
+            Recurrence::Monthly { day } => write!(f, "Monthly on day {day}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct TodoTask {
     pub id: usize,
     pub name: String,
+    pub completed_at: Option<u64>,
+    // expected pomodoros
+    pub estimate: Option<u32>,
+    pub priority: Priority,
+    pub parent: Option<usize>,
+    // sub-tasks in the list, in list order
+    pub children: Vec<usize>,
+    pub due: Option<NaiveDate>,
+    // checking the task off adds it again with the next due date
+    pub recurrence: Option<Recurrence>,
+    // recorded on sessions with this task picked
+    pub worked_secs: u64,
+    pub pomodoros: u32,
+}
+
+impl TodoTask {
+    // time and pomodoros spent on the task, against the estimate if there is one
+    pub fn summary(&self) -> Option<String> {
+        let pomodoros = match self.estimate {
+            Some(estimate) => format!("{}/{estimate}", self.pomodoros),
+            None => self.pomodoros.to_string(),
+        };
+        if self.worked_secs > 0 {
+            Some(format!(
+                "{} · {pomodoros}",
+                util::format_duration(self.worked_secs)
+            ))
+        } else {
+            self.estimate.map(|_| pomodoros)
+        }
+    }
+    // e.g. "Oct 20, Weekly on Monday"
+    pub fn schedule(&self) -> Option<String> {
+        match (self.due, self.recurrence) {
+            (Some(due), Some(recurrence)) => {
+                Some(format!("{}, {recurrence}", due.format("%b %-d")))
+            }
+            (Some(due), None) => Some(due.format("%b %-d").to_string()),
+            (None, Some(recurrence)) => Some(recurrence.to_string()),
+            (None, None) => None,
+        }
+    }
+    // open and due today or before
+    pub fn is_due(&self, today: NaiveDate) -> bool {
+        self.completed_at.is_none() && self.due.is_some_and(|due| due <= today)
+    }
 }
 
 impl Display for TodoTask {
@@ -19,39 +238,429 @@ impl Display for TodoTask {
 pub struct TodoTasks {
     project_id: Option<usize>,
     tasks: Vec<TodoTask>,
+    show_completed: bool,
+    // the tasks checked off last, for undo
+    last_completed: Vec<usize>,
+    // the task being worked on, recorded with the sessions
+    current: Option<usize>,
+    // the task last clicked or edited, moved by the keyboard
+    selected: Option<usize>,
+    // tasks with their sub-tasks hidden
+    collapsed: HashSet<usize>,
+    day_end_offset_hours: u32,
+}
+
+fn recurse<'a>(
+    task: &'a TodoTask,
+    all_tasks: &'a [TodoTask],
+    collapsed: &HashSet<usize>,
+    depth: usize,
+) -> Vec<(usize, &'a TodoTask)> {
+    let children: &[usize] = if collapsed.contains(&task.id) {
+        &[]
+    } else {
+        &task.children
+    };
+    iter::once((depth, task))
+        .chain(
+            children
+                .iter()
+                .flat_map(|id| all_tasks.iter().find(|t| t.id == *id))
+                .flat_map(|t| recurse(t, all_tasks, collapsed, depth + 1)),
+        )
+        .collect()
 }
 
 impl TodoTasks {
-    pub fn new(conn: &Connection, project_id: Option<usize>) -> Self {
+    pub fn new(conn: &Connection, project_id: Option<usize>, day_end_offset_hours: u32) -> Self {
         let mut t = TodoTasks {
             project_id,
             tasks: vec![],
+            show_completed: false,
+            last_completed: vec![],
+            current: None,
+            selected: None,
+            collapsed: HashSet::new(),
+            day_end_offset_hours,
         };
         t.fetch(conn);
         t
     }
     pub fn switch_project(&mut self, conn: &Connection, project_id: Option<usize>) {
         self.project_id = project_id;
+        self.last_completed.clear();
+        self.current = None;
+        self.selected = None;
         self.fetch(conn);
     }
     pub fn fetch(&mut self, conn: &Connection) {
         self.tasks.truncate(0);
-        self.tasks
-            .append(&mut db::get_tasks(conn, self.project_id).expect("Failed to fetch tasks"));
+        self.tasks.append(
+            &mut db::get_tasks(conn, self.project_id, self.show_completed)
+                .expect("Failed to fetch tasks"),
+        );
+        let parents: Vec<(usize, Option<usize>)> =
+            self.tasks.iter().map(|t| (t.id, t.parent)).collect();
+        for task in self.tasks.iter_mut() {
+            task.children = parents
+                .iter()
+                .filter(|(_, parent)| *parent == Some(task.id))
+                .map(|(id, _)| *id)
+                .collect();
+        }
     }
     pub fn get_all(&self) -> &Vec<TodoTask> {
         &self.tasks
     }
-    pub fn add(&mut self, name: String, project_id: Option<usize>, conn: &Connection) {
-        let _id = db::add_task(conn, name, project_id).expect("Failed to add task");
+    // due dates go by the day end offset
+    pub fn set_day_end_offset_hours(&mut self, day_end_offset_hours: u32) {
+        self.day_end_offset_hours = day_end_offset_hours;
+    }
+    pub fn today(&self) -> NaiveDate {
+        stats::today(self.day_end_offset_hours)
+    }
+    // the parent if it is in the list, sub-tasks of hidden tasks show at the top level
+    fn shown_parent(&self, task: &TodoTask) -> Option<usize> {
+        task.parent
+            .filter(|parent| self.tasks.iter().any(|t| t.id == *parent))
+    }
+    // tasks with their depth, sub-tasks of collapsed tasks left out
+    pub fn get_all_tree_style(&self) -> Vec<(usize, &TodoTask)> {
+        self.tasks
+            .iter()
+            .filter(|t| self.shown_parent(t).is_none())
+            .flat_map(|t| recurse(t, &self.tasks, &self.collapsed, 0))
+            .collect()
+    }
+    pub fn is_collapsed(&self, id: usize) -> bool {
+        self.collapsed.contains(&id)
+    }
+    pub fn set_collapsed(&mut self, id: usize, collapsed: bool) {
+        if collapsed {
+            self.collapsed.insert(id);
+        } else {
+            self.collapsed.remove(&id);
+        }
+    }
+    pub fn get_current(&self) -> Option<usize> {
+        self.current
+    }
+    pub fn get_current_task(&self) -> Option<&TodoTask> {
+        self.current
+            .and_then(|id| self.tasks.iter().find(|t| t.id == id))
+    }
+    pub fn set_current(&mut self, id: Option<usize>) {
+        self.current = id;
+    }
+    pub fn add(
+        &mut self,
+        name: String,
+        project_id: Option<usize>,
+        parent: Option<usize>,
+        conn: &Connection,
+    ) {
+        let _id = db::add_task(conn, name, project_id, parent).expect("Failed to add task");
         self.fetch(conn);
     }
     pub fn edit(&mut self, id: usize, name: String, conn: &Connection) {
         db::update_task(conn, id, name).expect("Failed to edit task");
         self.fetch(conn);
     }
-    pub fn delete(&mut self, id: usize, conn: &Connection) {
-        db::delete_task(conn, id).expect("Failed to delete task");
+    pub fn set_priority(&mut self, id: usize, priority: Priority, conn: &Connection) {
+        db::update_task_priority(conn, id, priority).expect("Failed to set task priority");
         self.fetch(conn);
     }
+    pub fn get_selected(&self) -> Option<usize> {
+        self.selected
+    }
+    pub fn set_selected(&mut self, id: Option<usize>) {
+        self.selected = id;
+    }
+    // Puts the task where the target task is, shifting the ones in between. Tasks sort by
+    // priority first, so a task moved among others of another priority takes on theirs.
+    // Only tasks under the same parent trade places
+    pub fn move_to(&mut self, id: usize, target_id: usize, conn: &Connection) {
+        let from = self.tasks.iter().position(|t| t.id == id);
+        let to = self.tasks.iter().position(|t| t.id == target_id);
+        let (Some(from), Some(to)) = (from, to) else {
+            return;
+        };
+        let target = &self.tasks[to];
+        // completed tasks stay below the open ones
+        if from == to
+            || self.tasks[from].completed_at.is_some() != target.completed_at.is_some()
+            || self.shown_parent(&self.tasks[from]) != self.shown_parent(target)
+        {
+            return;
+        }
+        let priority = target.priority;
+        let task = self.tasks.remove(from);
+        if task.priority != priority {
+            db::update_task_priority(conn, id, priority).expect("Failed to set task priority");
+        }
+        self.tasks.insert(to, task);
+        let ids: Vec<usize> = self.tasks.iter().map(|t| t.id).collect();
+        db::set_task_positions(conn, &ids).expect("Failed to reorder tasks");
+        self.fetch(conn);
+    }
+    // moves the selected task one place up or down among its siblings
+    pub fn move_selected(&mut self, up: bool, conn: &Connection) {
+        let Some(task) = self
+            .selected
+            .and_then(|id| self.tasks.iter().find(|t| t.id == id))
+        else {
+            return;
+        };
+        let parent = self.shown_parent(task);
+        let siblings: Vec<usize> = self
+            .tasks
+            .iter()
+            .filter(|t| self.shown_parent(t) == parent)
+            .map(|t| t.id)
+            .collect();
+        let id = task.id;
+        let Some(index) = siblings.iter().position(|s| *s == id) else {
+            return;
+        };
+        let target = if up {
+            index.checked_sub(1)
+        } else {
+            Some(index + 1)
+        };
+        if let Some(target) = target.and_then(|i| siblings.get(i)) {
+            self.move_to(id, *target, conn);
+        }
+    }
+    pub fn set_estimate(&mut self, id: usize, estimate: Option<u32>, conn: &Connection) {
+        db::update_task_estimate(conn, id, estimate).expect("Failed to set task estimate");
+        self.fetch(conn);
+    }
+    // monthly recurrence follows the day of the new due date
+    pub fn set_due(&mut self, id: usize, due: Option<NaiveDate>, conn: &Connection) {
+        db::update_task_due(conn, id, due).expect("Failed to set task due date");
+        let task = self.tasks.iter().find(|t| t.id == id);
+        if let (Some(due), Some(Recurrence::Monthly { .. })) =
+            (due, task.and_then(|t| t.recurrence))
+        {
+            let recurrence = Recurrence::Monthly { day: due.day() };
+            db::update_task_recurrence(conn, id, Some(recurrence))
+                .expect("Failed to set task recurrence");
+        }
+        self.fetch(conn);
+    }
+    pub fn set_recurrence(&mut self, id: usize, recurrence: Option<Recurrence>, conn: &Connection) {
+        db::update_task_recurrence(conn, id, recurrence).expect("Failed to set task recurrence");
+        self.fetch(conn);
+    }
+    pub fn get_show_completed(&self) -> bool {
+        self.show_completed
+    }
+    pub fn set_show_completed(&mut self, show_completed: bool, conn: &Connection) {
+        self.show_completed = show_completed;
+        self.fetch(conn);
+    }
+    pub fn get_last_completed(&self) -> &[usize] {
+        &self.last_completed
+    }
+    pub fn get_open_subtasks(&self, id: usize, conn: &Connection) -> Vec<usize> {
+        db::get_open_subtasks(conn, id).expect("Failed to fetch sub-tasks")
+    }
+    // Checks off the tasks together, they are undone together too. Recurring tasks are added
+    // again due next time, the recurrence moves over to the new task
+    pub fn complete(&mut self, ids: &[usize], conn: &Connection) {
+        let now = Local::now().timestamp() as u64;
+        let today = self.today();
+        for id in ids {
+            db::set_task_completed(conn, *id, Some(now)).expect("Failed to complete task");
+            let task = self.tasks.iter().find(|t| t.id == *id);
+            if let Some((due, recurrence)) = task.and_then(|t| t.recurrence.map(|r| (t.due, r))) {
+                db::repeat_task(conn, *id, recurrence.next_due(due, today))
+                    .expect("Failed to repeat task");
+                db::update_task_recurrence(conn, *id, None).expect("Failed to repeat task");
+            }
+        }
+        self.last_completed = ids.to_vec();
+        // nothing is left to do on it
+        if self.current.is_some_and(|id| ids.contains(&id)) {
+            self.current = None;
+        }
+        self.fetch(conn);
+    }
+    // the task added to repeat a recurring one goes away again, unless it was worked on already
+    pub fn reopen(&mut self, id: usize, conn: &Connection) {
+        db::set_task_completed(conn, id, None).expect("Failed to reopen task");
+        db::unrepeat_task(conn, id).expect("Failed to reopen task");
+        self.last_completed.retain(|last| *last != id);
+        self.fetch(conn);
+    }
+    // For typos and tasks added by mistake, completing them would count them as done.
+    // Returns the ids of the task and its sub-tasks
+    pub fn delete(&mut self, id: usize, conn: &Connection) -> Vec<usize> {
+        let deleted = db::delete_task(conn, id).expect("Failed to delete task");
+        self.last_completed.retain(|id| !deleted.contains(id));
+        if self.current.is_some_and(|id| deleted.contains(&id)) {
+            self.current = None;
+        }
+        if self.selected.is_some_and(|id| deleted.contains(&id)) {
+            self.selected = None;
+        }
+        self.fetch(conn);
+        deleted
+    }
+    pub fn undo_complete(&mut self, conn: &Connection) {
+        for id in std::mem::take(&mut self.last_completed) {
+            db::set_task_completed(conn, id, None).expect("Failed to reopen task");
+            db::unrepeat_task(conn, id).expect("Failed to reopen task");
+        }
+        self.fetch(conn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn monthly_clamps_to_shorter_months_and_keeps_the_day() {
+        let monthly = Recurrence::Monthly { day: 31 };
+        let mut due = date("2026-01-31");
+        let mut chain = vec![];
+        for _ in 0..5 {
+            due = monthly.next_due(Some(due), due);
+            chain.push(due);
+        }
+        assert_eq!(
+            chain,
+            [
+                "2026-02-28",
+                "2026-03-31",
+                "2026-04-30",
+                "2026-05-31",
+                "2026-06-30"
+            ]
+            .map(date)
+        );
+        // leap years get the 29th
+        assert_eq!(
+            monthly.next_due(Some(date("2028-01-31")), date("2028-01-31")),
+            date("2028-02-29")
+        );
+        // still due later this month
+        assert_eq!(
+            monthly.next_due(Some(date("2026-01-10")), date("2026-01-09")),
+            date("2026-01-31")
+        );
+    }
+
+    #[test]
+    fn weekly_wraps_around_the_week() {
+        // 2026-10-16 is a Friday
+        let friday = date("2026-10-16");
+        assert_eq!(
+            Recurrence::Weekly(Weekday::Mon).next_due(Some(friday), friday),
+            date("2026-10-19")
+        );
+        // the same weekday comes a week later
+        assert_eq!(
+            Recurrence::Weekly(Weekday::Fri).next_due(Some(friday), friday),
+            date("2026-10-23")
+        );
+        assert_eq!(
+            Recurrence::Weekdays.next_due(Some(friday), friday),
+            date("2026-10-19")
+        );
+        // missed occurrences are skipped
+        assert_eq!(
+            Recurrence::Weekly(Weekday::Wed).next_due(Some(friday), date("2026-11-02")),
+            date("2026-11-04")
+        );
+    }
+
+    #[test]
+    fn recurrence_strings_round_trip() {
+        for r in Recurrence::options(date("2026-10-31")) {
+            assert_eq!(Recurrence::parse(&r.as_string()), Some(r));
+        }
+        assert_eq!(Recurrence::parse("monthly 32"), None);
+        assert_eq!(Recurrence::parse("monthly"), None);
+    }
+
+    // due far ahead, so the repeat does not depend on today
+    fn recurring_task(conn: &Connection) -> (TodoTasks, usize) {
+        let mut tasks = TodoTasks::new(conn, None, 0);
+        tasks.set_show_completed(true, conn);
+        tasks.add("Rent".to_owned(), None, None, conn);
+        let id = tasks.get_all()[0].id;
+        tasks.set_due(id, Some(date("2099-01-31")), conn);
+        tasks.set_recurrence(id, Some(Recurrence::Monthly { day: 31 }), conn);
+        (tasks, id)
+    }
+
+    #[test]
+    fn completing_repeats_and_reopening_takes_the_repeat_back() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn, None).unwrap();
+        let (mut tasks, id) = recurring_task(&conn);
+
+        tasks.complete(&[id], &conn);
+        let all = tasks.get_all();
+        assert_eq!(all.len(), 2);
+        let repeat = all.iter().find(|t| t.id != id).unwrap();
+        assert_eq!(repeat.due, Some(date("2099-02-28")));
+        assert_eq!(repeat.recurrence, Some(Recurrence::Monthly { day: 31 }));
+        assert_eq!(all.iter().find(|t| t.id == id).unwrap().recurrence, None);
+
+        tasks.reopen(id, &conn);
+        let all = tasks.get_all();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].completed_at, None);
+        assert_eq!(all[0].due, Some(date("2099-01-31")));
+        assert_eq!(all[0].recurrence, Some(Recurrence::Monthly { day: 31 }));
+
+        // undo does the same
+        tasks.complete(&[id], &conn);
+        tasks.undo_complete(&conn);
+        assert_eq!(tasks.get_all().len(), 1);
+        assert_eq!(
+            tasks.get_all()[0].recurrence,
+            Some(Recurrence::Monthly { day: 31 })
+        );
+    }
+
+    #[test]
+    fn reopening_keeps_a_repeat_already_worked_on() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::migrate(&mut conn, None).unwrap();
+        let (mut tasks, id) = recurring_task(&conn);
+
+        tasks.complete(&[id], &conn);
+        let repeat = tasks.get_all().iter().find(|t| t.id != id).unwrap().id;
+        db::add_work_session(
+            &conn,
+            &db::WorkSession {
+                time_start: 1_800_000_000,
+                duration: 1500,
+                project_id: None,
+                task_id: Some(repeat),
+                completed: true,
+            },
+        )
+        .unwrap();
+        tasks.reopen(id, &conn);
+        assert_eq!(tasks.get_all().len(), 2);
+        assert_eq!(
+            tasks
+                .get_all()
+                .iter()
+                .find(|t| t.id == id)
+                .unwrap()
+                .recurrence,
+            None
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Local, NaiveDate, TimeDelta};
+
 pub fn truncate_with_ellipsis(mut s: String, max_len: usize) -> String {
     let upto = s.char_indices().map(|(i, _)| i).nth(max_len);
     if let Some(upto) = upto {
@@ -6,3 +8,34 @@ pub fn truncate_with_ellipsis(mut s: String, max_len: usize) -> String {
     }
     s
 }
+
+// "1h 25m" or "25m"
+pub fn format_duration(secs: u64) -> String {
+    let minutes = (secs + 30) / 60;
+    if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+pub fn local_time(timestamp: u64) -> DateTime<Local> {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+// the day a session counts towards, the day ends config_offset_hours after midnight
+pub fn session_day(time_start: u64, config_offset_hours: u32) -> NaiveDate {
+    local_time(time_start)
+        .checked_sub_signed(TimeDelta::hours((config_offset_hours % 24) as i64))
+        .unwrap()
+        .date_naive()
+}
+
+// whether start..end overlaps any of the ranges, all in unix seconds
+pub fn overlaps_any(start: u64, end: u64, ranges: &[(u64, u64)]) -> bool {
+    ranges
+        .iter()
+        .any(|(range_start, range_end)| start < *range_end && end > *range_start)
+}